@@ -0,0 +1,91 @@
+//! Time to render a frame of the teapot as big as a full screen terminal, both
+//! on an unbounded canvas and on one limited to the screen. Run with
+//! `--features rayon` to fill the triangles in parallel.
+
+use std::io;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use termesh::bounds::BoundingSphere;
+use termesh::camera::Camera;
+use termesh::drawille::Canvas;
+use termesh::mesh::IndexedMesh;
+use termesh::stl::Stl;
+use termesh::Vector3;
+
+// size of the screen in cells
+const WIDTH: i32 = 200;
+const HEIGHT: i32 = 60;
+
+// the triangles of the teapot projected so that it fills the screen
+fn teapot() -> Vec<[Vector3; 3]> {
+    let teapot_stl = include_bytes!("../data/teapot.stl");
+    let stl = Stl::parse_binary(&mut io::Cursor::new(&teapot_stl[..])).unwrap();
+    let mesh = IndexedMesh::from(&stl);
+
+    let sphere = BoundingSphere::from_points(&mesh.vertices).unwrap();
+    let scale = (HEIGHT * 2) as f32 / sphere.radius;
+
+    let mut camera = Camera::new();
+    camera.target = sphere.center * scale;
+    camera.position = camera.target + Vector3::new(0.0, 0.0, -1.0);
+    camera.fit(sphere.radius * scale);
+
+    let project = camera.projector();
+    mesh.triangles
+        .iter()
+        .map(|t| {
+            let [a, b, c] = *t;
+            [
+                project(mesh.vertices[a] * scale),
+                project(mesh.vertices[b] * scale),
+                project(mesh.vertices[c] * scale),
+            ]
+        })
+        .collect()
+}
+
+fn render(mut canvas: Canvas, triangles: &[[Vector3; 3]]) -> Vec<String> {
+    let front = triangles
+        .iter()
+        .filter(|&&[a, b, c]| (b - a).cross(&(c - a)).z <= 0.0)
+        .map(|&t| (t, None))
+        .collect::<Vec<_>>();
+
+    canvas.fill_triangles(&front);
+
+    let min_r = -(HEIGHT - 1) / 2;
+    let min_c = -WIDTH / 2;
+
+    canvas
+        .frame(
+            true,
+            min_r,
+            min_r + HEIGHT - 2,
+            min_c,
+            Some(min_c + WIDTH - 1),
+        )
+        .collect()
+}
+
+fn bench_teapot(c: &mut Criterion) {
+    let triangles = teapot();
+
+    let min_r = -(HEIGHT - 1) / 2;
+    let min_c = -WIDTH / 2;
+
+    let mut group = c.benchmark_group("teapot");
+    group.bench_function("unbounded", |b| {
+        b.iter(|| render(Canvas::new(), &triangles))
+    });
+    group.bench_function("viewport", |b| {
+        b.iter(|| {
+            let canvas = Canvas::with_viewport(min_r, min_r + HEIGHT - 2, min_c, min_c + WIDTH - 1);
+            render(canvas, &triangles)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_teapot);
+criterion_main!(benches);
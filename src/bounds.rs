@@ -0,0 +1,176 @@
+//! Simple bounding volumes for sets of points.
+
+use crate::Vector3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BoundingBox {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl BoundingBox {
+    /// Create the smallest axis aligned box that contains all the given
+    /// points, `None` if there are no points.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vector3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+
+        Some(points.fold(
+            BoundingBox {
+                min: first,
+                max: first,
+            },
+            |bbox, p| BoundingBox {
+                min: Vector3::new(
+                    bbox.min.x.min(p.x),
+                    bbox.min.y.min(p.y),
+                    bbox.min.z.min(p.z),
+                ),
+                max: Vector3::new(
+                    bbox.max.x.max(p.x),
+                    bbox.max.y.max(p.y),
+                    bbox.max.z.max(p.z),
+                ),
+            },
+        ))
+    }
+
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vector3 {
+        self.max - self.min
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BoundingSphere {
+    pub center: Vector3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Create the smallest sphere centered at `center` that contains all the
+    /// given points. Unlike bounding boxes, the sphere doesn't change when
+    /// the points are rotated around its center.
+    pub fn around<'a>(center: Vector3, points: impl IntoIterator<Item = &'a Vector3>) -> Self {
+        let radius = points
+            .into_iter()
+            .fold(0.0_f32, |r, p| r.max((*p - center).norm()));
+
+        BoundingSphere { center, radius }
+    }
+
+    /// Create a sphere that contains all the given points centered at the
+    /// center of their bounding box, `None` if there are no points.
+    pub fn from_points<'a, I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = &'a Vector3>,
+        I::IntoIter: Clone,
+    {
+        let points = points.into_iter();
+        let bbox = BoundingBox::from_points(points.clone())?;
+
+        Some(BoundingSphere::around(bbox.center(), points))
+    }
+}
+
+/// The average of the given points, `None` if there are no points.
+pub fn centroid<'a>(points: impl IntoIterator<Item = &'a Vector3>) -> Option<Vector3> {
+    let (sum, n) = points
+        .into_iter()
+        .fold((Vector3::new(0.0, 0.0, 0.0), 0_u32), |(sum, n), p| {
+            (sum + *p, n + 1)
+        });
+
+    if n == 0 {
+        None
+    } else {
+        Some(sum / n as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{centroid, BoundingBox, BoundingSphere, Vector3};
+
+    #[test]
+    fn test_bounding_box() {
+        assert_eq!(BoundingBox::from_points(&[]), None);
+
+        let bbox = BoundingBox::from_points(&[
+            Vector3::new(1.0, -2.0, 0.0),
+            Vector3::new(3.0, 2.0, 0.0),
+            Vector3::new(2.0, 0.0, -4.0),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            bbox,
+            BoundingBox {
+                min: Vector3::new(1.0, -2.0, -4.0),
+                max: Vector3::new(3.0, 2.0, 0.0),
+            }
+        );
+        assert_eq!(bbox.center(), Vector3::new(2.0, 0.0, -2.0));
+        assert_eq!(bbox.size(), Vector3::new(2.0, 4.0, 4.0));
+    }
+
+    #[test]
+    fn test_bounding_sphere() {
+        assert_eq!(BoundingSphere::from_points(&[]), None);
+
+        let points = [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(-3.0, 0.0, 0.0),
+            Vector3::new(-1.0, 1.0, 0.0),
+        ];
+
+        assert_eq!(
+            BoundingSphere::from_points(&points),
+            Some(BoundingSphere {
+                center: Vector3::new(-1.0, 0.5, 0.0),
+                radius: (4.0_f32 + 0.25).sqrt(),
+            })
+        );
+
+        assert_eq!(
+            BoundingSphere::around(Vector3::new(0.0, 0.0, 0.0), &points),
+            BoundingSphere {
+                center: Vector3::new(0.0, 0.0, 0.0),
+                radius: 3.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_huge_bounding_sphere() {
+        let points = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 1.0),
+            Vector3::new(1e30, 0.0, 0.0),
+            Vector3::new(1e30, 1.0, 0.0),
+            Vector3::new(1e30, 0.0, 1.0),
+        ];
+
+        let sphere = BoundingSphere::from_points(&points).unwrap();
+        assert_eq!(sphere.center, Vector3::new(5e29, 0.5, 0.5));
+        assert_eq!(sphere.radius, 5e29);
+    }
+
+    #[test]
+    fn test_centroid() {
+        assert_eq!(centroid(&[]), None);
+
+        assert_eq!(
+            centroid(&[
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(3.0, 6.0, -9.0),
+            ]),
+            Some(Vector3::new(1.0, 2.0, -3.0))
+        );
+    }
+}
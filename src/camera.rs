@@ -0,0 +1,343 @@
+//! A simple Camera used to project the scene before it reaches the canvas.
+//!
+//! Projected points are still in canvas space, that is the X and Y
+//! coordinates can be passed to the canvas as is, while the Z coordinate is
+//! the depth relative to the plane of the target: the smaller the closer to
+//! the camera.
+
+use crate::{Matrix4, Vector3};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    Orthographic,
+    Perspective,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub position: Vector3,
+    pub target: Vector3,
+    pub up: Vector3,
+
+    // vertical field of view in radians
+    pub fov: f32,
+
+    pub near: f32,
+    pub far: f32,
+
+    pub projection: Projection,
+}
+
+impl Camera {
+    /// Create an orthographic Camera that looks at the origin from the
+    /// negative Z axis with the Y axis pointing down, that is the default
+    /// canvas orientation.
+    pub fn new() -> Self {
+        Camera {
+            position: Vector3::new(0.0, 0.0, -1.0),
+            target: Vector3::new(0.0, 0.0, 0.0),
+            up: Vector3::new(0.0, -1.0, 0.0),
+            fov: 60_f32.to_radians(),
+            near: 0.1,
+            far: 100.0,
+            projection: Projection::Orthographic,
+        }
+    }
+
+    /// Switch from orthographic to perspective projection and viceversa.
+    pub fn toggle_projection(&mut self) {
+        self.projection = match self.projection {
+            Projection::Orthographic => Projection::Perspective,
+            Projection::Perspective => Projection::Orthographic,
+        };
+    }
+
+    /// Distance between the camera and its target
+    pub fn distance(&self) -> f32 {
+        (self.target - self.position).norm()
+    }
+
+    /// Move the camera along its viewing direction so that a sphere centered
+    /// at the target with the given radius fits the field of view. The near
+    /// and far planes are adjusted to enclose the sphere. A radius that is
+    /// not positive or not finite is rejected in favour of a unit sphere.
+    pub fn fit(&mut self, radius: f32) {
+        let radius = if radius > 0.0 && radius.is_finite() {
+            radius
+        } else {
+            1.0
+        };
+
+        let mut dir = (self.position - self.target).normalized();
+        if dir.norm() == 0.0 {
+            dir = Vector3::new(0.0, 0.0, -1.0);
+        }
+
+        let distance = radius / (self.fov / 2.0).sin();
+
+        self.position = self.target + dir * distance;
+        self.near = (distance - radius).max(distance * 0.01);
+        self.far = distance + radius;
+    }
+
+    pub fn view_matrix(&self) -> Matrix4 {
+        Matrix4::look_at(self.position, self.target, self.up)
+    }
+
+    /// The projection matrix maps points in view space to the [-1, 1] range
+    /// on the X and Y axes. The visible area on the plane of the target is
+    /// a square with side `2 * half_height()` in both projections.
+    pub fn projection_matrix(&self) -> Matrix4 {
+        match self.projection {
+            Projection::Perspective => Matrix4::perspective(self.fov, 1.0, self.near, self.far),
+            Projection::Orthographic => {
+                let h = self.half_height();
+                Matrix4::orthographic(-h, h, -h, h, self.near, self.far)
+            }
+        }
+    }
+
+    /// Half of the height of the visible area on the plane of the target.
+    pub fn half_height(&self) -> f32 {
+        self.distance() * (self.fov / 2.0).tan()
+    }
+
+    /// Project the given point in canvas space. Prefer `projector` when
+    /// projecting many points.
+    pub fn project(&self, p: Vector3) -> Vector3 {
+        self.projector()(p)
+    }
+
+    /// Get a function that projects points in canvas space. Objects on the
+    /// plane of the target keep the same size regardless of the projection.
+    pub fn projector(&self) -> impl Fn(Vector3) -> Vector3 {
+        // work relative to the target to not lose precision, the default
+        // camera must leave points untouched.
+        let orientation = Matrix4::look_at(
+            Vector3::new(0.0, 0.0, 0.0),
+            self.target - self.position,
+            self.up,
+        );
+        let target = self.target;
+        let distance = self.distance();
+
+        let projection = self.projection_matrix();
+        let half_height = self.half_height();
+        let perspective = self.projection == Projection::Perspective;
+
+        move |p| {
+            let v = orientation.transform_point(p - target);
+
+            if !perspective {
+                return v;
+            }
+
+            let ndc = projection.transform_point(Vector3::new(v.x, v.y, v.z + distance));
+            Vector3::new(ndc.x * half_height, ndc.y * half_height, v.z)
+        }
+    }
+
+    /// Whether the given point is in front of the near plane of the camera.
+    /// Orthographic cameras see everything, points behind them don't get
+    /// distorted by the projection.
+    pub fn sees(&self, p: Vector3) -> bool {
+        self.near_distance(p) >= 0.0
+    }
+
+    /// Clip the segment between the given points to the part in front of the
+    /// near plane, `None` if it's entirely behind it.
+    pub fn clip_segment(&self, p0: Vector3, p1: Vector3) -> Option<(Vector3, Vector3)> {
+        let (d0, d1) = (self.near_distance(p0), self.near_distance(p1));
+
+        match (d0 >= 0.0, d1 >= 0.0) {
+            (true, true) => Some((p0, p1)),
+            (false, false) => None,
+            (true, false) => Some((p0, lerp(p0, p1, d0 / (d0 - d1)))),
+            (false, true) => Some((lerp(p0, p1, d0 / (d0 - d1)), p1)),
+        }
+    }
+
+    /// Clip the triangle with the given vertices to the part in front of the
+    /// near plane. The result is a polygon of at most 4 vertices in the same
+    /// winding order, each with its barycentric coordinates in the triangle
+    /// to interpolate the attributes of the vertices.
+    pub fn clip_triangle(&self, triangle: [Vector3; 3]) -> Vec<(Vector3, [f32; 3])> {
+        let corners = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let mut polygon = Vec::with_capacity(4);
+
+        for i in 0..3 {
+            let j = (i + 1) % 3;
+            let (pi, pj) = (triangle[i], triangle[j]);
+            let (di, dj) = (self.near_distance(pi), self.near_distance(pj));
+
+            if di >= 0.0 {
+                polygon.push((pi, corners[i]));
+            }
+
+            // the edge crosses the plane
+            if (di >= 0.0) != (dj >= 0.0) {
+                let t = di / (di - dj);
+
+                let mut w = [0.0; 3];
+                w[i] = 1.0 - t;
+                w[j] = t;
+
+                polygon.push((lerp(pi, pj, t), w));
+            }
+        }
+
+        polygon
+    }
+
+    // signed distance between the given point and the near plane, positive if
+    // the point is in front of it.
+    fn near_distance(&self, p: Vector3) -> f32 {
+        if self.projection == Projection::Orthographic {
+            return f32::INFINITY;
+        }
+
+        let forward = (self.target - self.position).normalized();
+        (p - self.position).dot(&forward) - self.near
+    }
+}
+
+fn lerp(a: Vector3, b: Vector3, t: f32) -> Vector3 {
+    a + (b - a) * t
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Camera, Projection, Vector3};
+    use crate::bounds::BoundingSphere;
+
+    fn round(v: Vector3) -> Vector3 {
+        (v * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn test_orthographic() {
+        let camera = Camera::new();
+
+        assert_eq!(
+            camera.project(Vector3::new(3.0, -2.0, 5.0)),
+            Vector3::new(3.0, -2.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn test_perspective() {
+        let mut camera = Camera::new();
+        camera.projection = Projection::Perspective;
+        camera.fit(10.0);
+
+        let d = camera.distance();
+        assert_eq!((d * 1000.0).round(), 20000.0);
+
+        // points on the target plane are not affected by the projection
+        assert_eq!(
+            round(camera.project(Vector3::new(3.0, -2.0, 0.0))),
+            Vector3::new(3.0, -2.0, 0.0)
+        );
+
+        // closer points are bigger, farther ones are smaller
+        assert_eq!(
+            round(camera.project(Vector3::new(3.0, -2.0, -10.0))),
+            Vector3::new(6.0, -4.0, -10.0)
+        );
+        assert_eq!(
+            round(camera.project(Vector3::new(3.0, -2.0, 20.0))),
+            Vector3::new(1.5, -1.0, 20.0)
+        );
+    }
+
+    #[test]
+    fn test_clip_near() {
+        let mut camera = Camera::new();
+        camera.projection = Projection::Perspective;
+        camera.fit(10.0);
+
+        // the near plane is at z = -10
+        assert!(camera.sees(Vector3::new(0.0, 0.0, -9.0)));
+        assert!(!camera.sees(Vector3::new(0.0, 0.0, -11.0)));
+
+        assert_eq!(
+            camera
+                .clip_segment(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 4.0, -20.0))
+                .map(|(p0, p1)| (round(p0), round(p1))),
+            Some((Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 2.0, -10.0)))
+        );
+        assert_eq!(
+            camera.clip_segment(Vector3::new(0.0, 0.0, -11.0), Vector3::new(0.0, 0.0, -20.0)),
+            None
+        );
+
+        // a corner behind the near plane turns the triangle into a quad
+        let clipped = camera.clip_triangle([
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(4.0, 0.0, -20.0),
+            Vector3::new(0.0, 4.0, 0.0),
+        ]);
+        assert_eq!(
+            clipped
+                .into_iter()
+                .map(|(p, w)| (round(p), w))
+                .collect::<Vec<_>>(),
+            vec![
+                (Vector3::new(0.0, 0.0, 0.0), [1.0, 0.0, 0.0]),
+                (Vector3::new(2.0, 0.0, -10.0), [0.5, 0.5, 0.0]),
+                (Vector3::new(2.0, 2.0, -10.0), [0.0, 0.5, 0.5]),
+                (Vector3::new(0.0, 4.0, 0.0), [0.0, 0.0, 1.0]),
+            ]
+        );
+
+        // orthographic cameras see everything
+        camera.toggle_projection();
+        assert!(camera.sees(Vector3::new(0.0, 0.0, -100.0)));
+    }
+
+    #[test]
+    fn test_fit_huge() {
+        let mut camera = Camera::new();
+        camera.fit(f32::INFINITY);
+        assert_eq!((camera.distance() * 1000.0).round(), 2000.0);
+
+        // a facet way far away next to a unit one
+        let points = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(1e30, 0.0, 0.0),
+            Vector3::new(1e30, 1.0, 0.0),
+            Vector3::new(1e30, 0.0, 1.0),
+        ];
+        let bounds = BoundingSphere::from_points(&points).unwrap();
+
+        for &projection in &[Projection::Orthographic, Projection::Perspective] {
+            let mut camera = Camera::new();
+            camera.projection = projection;
+            camera.target = bounds.center;
+            camera.position = bounds.center + Vector3::new(0.0, 0.0, -1.0);
+            camera.fit(bounds.radius);
+
+            assert!(camera.half_height().is_finite());
+            assert!(points.iter().all(|&p| camera.project(p).is_finite()));
+        }
+    }
+
+    #[test]
+    fn test_toggle_projection() {
+        let mut camera = Camera::new();
+
+        camera.toggle_projection();
+        assert_eq!(camera.projection, Projection::Perspective);
+
+        camera.toggle_projection();
+        assert_eq!(camera.projection, Projection::Orthographic);
+    }
+}
@@ -0,0 +1,167 @@
+//! The characters a `Canvas` can be rendered with. The canvas always keeps the
+//! 4x2 dots of a braille character for each cell, the other charsets group
+//! these dots in bigger sub pixels trading resolution for compatibility or
+//! colors.
+
+use super::BRAILLE_PATTERN_BLANK;
+
+static QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+static ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// 4x2 dots per cell, the highest resolution.
+    Braille,
+
+    /// Upper and lower half blocks, each half can have its own color.
+    HalfBlock,
+
+    /// 2x2 block elements per cell.
+    Quadrant,
+
+    /// 3x2 block elements per cell.
+    Sextant,
+
+    /// A character from ` .:-=+*#%@` per cell by how many dots are set and
+    /// how bright they are, it works in any terminal.
+    Ascii,
+}
+
+impl Charset {
+    /// The charset to switch to when cycling through all of them.
+    pub fn next(self) -> Self {
+        match self {
+            Charset::Braille => Charset::HalfBlock,
+            Charset::HalfBlock => Charset::Quadrant,
+            Charset::Quadrant => Charset::Sextant,
+            Charset::Sextant => Charset::Ascii,
+            Charset::Ascii => Charset::Braille,
+        }
+    }
+
+    // The dots that make up each sub pixel of a cell as masks of
+    // `BRAILLE_OFFSET_MAP`, in the order of the bits used by `glyph`. The 4
+    // rows of dots are split unevenly in the 3 rows of a sextant, the middle
+    // one gets two of them.
+    pub(super) fn subpixels(self) -> &'static [u8] {
+        match self {
+            Charset::Braille => &[0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80],
+            Charset::HalfBlock => &[0x1B, 0xE4],
+            Charset::Quadrant => &[0x03, 0x18, 0x44, 0xA0],
+            Charset::Sextant => &[0x01, 0x08, 0x06, 0x30, 0x40, 0x80],
+            Charset::Ascii => &[0xFF],
+        }
+    }
+
+    pub(super) fn blank(self) -> char {
+        match self {
+            Charset::Braille => BRAILLE_PATTERN_BLANK,
+            _ => ' ',
+        }
+    }
+
+    // The character with the given sub pixels set, see `subpixels`. For ascii
+    // the character only depends on the `density` of the cell in [0, 1]
+    // instead.
+    pub(super) fn glyph(self, bits: u8, density: f32) -> char {
+        match self {
+            Charset::Braille => {
+                std::char::from_u32(BRAILLE_PATTERN_BLANK as u32 + u32::from(bits)).unwrap()
+            }
+            Charset::HalfBlock => QUADRANTS[usize::from(((bits & 1) * 3) | ((bits >> 1) * 12))],
+            Charset::Quadrant => QUADRANTS[usize::from(bits)],
+            Charset::Sextant => sextant(bits),
+            Charset::Ascii => {
+                if bits == 0 {
+                    return ' ';
+                }
+
+                // any dot at all deserves at least the lightest character
+                let last = ASCII_RAMP.len() - 1;
+                let i = 1 + (density.clamp(0.0, 1.0) * (last - 1) as f32).round() as usize;
+                char::from(ASCII_RAMP[i])
+            }
+        }
+    }
+}
+
+// The sextants are in the "Symbols for Legacy Computing" block in the order of
+// their bits, except for the ones that already exist as block elements.
+fn sextant(bits: u8) -> char {
+    match bits {
+        0 => ' ',
+        0b01_0101 => '▌',
+        0b10_1010 => '▐',
+        0b11_1111 => '█',
+        _ => {
+            let skipped = u32::from(bits > 0b01_0101) + u32::from(bits > 0b10_1010);
+            std::char::from_u32(0x1FB00 + u32::from(bits) - 1 - skipped).unwrap()
+        }
+    }
+}
+
+impl std::str::FromStr for Charset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "braille" => Ok(Charset::Braille),
+            "half-block" => Ok(Charset::HalfBlock),
+            "quadrant" => Ok(Charset::Quadrant),
+            "sextant" => Ok(Charset::Sextant),
+            "ascii" => Ok(Charset::Ascii),
+            _ => Err(
+                "expected `braille`, `half-block`, `quadrant`, `sextant` or `ascii`".to_string(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Charset;
+
+    #[test]
+    fn test_glyphs() {
+        assert_eq!(Charset::Braille.glyph(0x09, 1.0), '⠉');
+
+        assert_eq!(Charset::HalfBlock.glyph(0b00, 1.0), ' ');
+        assert_eq!(Charset::HalfBlock.glyph(0b01, 1.0), '▀');
+        assert_eq!(Charset::HalfBlock.glyph(0b10, 1.0), '▄');
+        assert_eq!(Charset::HalfBlock.glyph(0b11, 1.0), '█');
+
+        assert_eq!(Charset::Quadrant.glyph(0b1001, 1.0), '▚');
+
+        assert_eq!(Charset::Sextant.glyph(0b00_0001, 1.0), '\u{1FB00}');
+        assert_eq!(Charset::Sextant.glyph(0b01_0100, 1.0), '\u{1FB13}');
+        assert_eq!(Charset::Sextant.glyph(0b01_0101, 1.0), '▌');
+        assert_eq!(Charset::Sextant.glyph(0b01_0110, 1.0), '\u{1FB14}');
+        assert_eq!(Charset::Sextant.glyph(0b10_1011, 1.0), '\u{1FB28}');
+        assert_eq!(Charset::Sextant.glyph(0b11_1110, 1.0), '\u{1FB3B}');
+        assert_eq!(Charset::Sextant.glyph(0b11_1111, 1.0), '█');
+
+        assert_eq!(Charset::Ascii.glyph(0, 1.0), ' ');
+        assert_eq!(Charset::Ascii.glyph(1, 0.0), '.');
+        assert_eq!(Charset::Ascii.glyph(1, 0.5), '+');
+        assert_eq!(Charset::Ascii.glyph(1, 1.0), '@');
+    }
+
+    #[test]
+    fn test_subpixels_cover_the_cell() {
+        for charset in &[
+            Charset::Braille,
+            Charset::HalfBlock,
+            Charset::Quadrant,
+            Charset::Sextant,
+            Charset::Ascii,
+        ] {
+            let subpixels = charset.subpixels();
+
+            assert_eq!(subpixels.iter().fold(0, |all, s| all | s), 0xFF);
+            assert_eq!(subpixels.iter().map(|s| s.count_ones()).sum::<u32>(), 8);
+        }
+    }
+}
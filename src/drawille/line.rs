@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::ops::Range;
 
 use crate::Vector3;
 
@@ -15,6 +16,17 @@ impl Line {
 
         let steps = dir.x.abs().max(dir.y.abs()).max(dir.z.abs());
 
+        // there's no way to step through a line that goes to infinity, or
+        // that is NaN for that matters. Even finite points can be too far
+        // apart to fit a f32.
+        if !p0.is_finite() || !p1.is_finite() || !steps.is_finite() {
+            return Self {
+                p0,
+                step: Vector3::new(0.0, 0.0, 0.0),
+                steps: 0,
+            };
+        }
+
         // if steps == 0.0 the line is actually just a point
         if steps == 0.0 {
             Self {
@@ -32,6 +44,94 @@ impl Line {
     }
 }
 
+/// Clip the segment between the given points to the rectangle between `min`
+/// and `max` on the XY plane, `None` if it's entirely outside. The clipped
+/// points lie exactly on the border of the rectangle, no matter how far away
+/// the original ones were.
+pub fn clip(
+    p0: Vector3,
+    p1: Vector3,
+    min: (f32, f32),
+    max: (f32, f32),
+) -> Option<(Vector3, Vector3)> {
+    let outcode = |p: Vector3| {
+        u8::from(p.x < min.0)
+            | u8::from(p.x > max.0) << 1
+            | u8::from(p.y < min.1) << 2
+            | u8::from(p.y > max.1) << 3
+    };
+
+    let (mut p0, mut p1) = (p0, p1);
+
+    // each point is moved at most once per axis, unless rounding errors get
+    // in the way.
+    for _ in 0..8 {
+        let (c0, c1) = (outcode(p0), outcode(p1));
+        if c0 | c1 == 0 {
+            return Some((p0, p1));
+        }
+        if c0 & c1 != 0 {
+            return None;
+        }
+
+        let (p, other, c) = if c0 != 0 {
+            (&mut p0, p1, c0)
+        } else {
+            (&mut p1, p0, c1)
+        };
+
+        *p = if c & 0b0011 != 0 {
+            let x = if c & 0b0001 != 0 { min.0 } else { max.0 };
+            let t = (x - p.x) / (other.x - p.x);
+            Vector3::new(x, p.y + (other.y - p.y) * t, p.z + (other.z - p.z) * t)
+        } else {
+            let y = if c & 0b0100 != 0 { min.1 } else { max.1 };
+            let t = (y - p.y) / (other.y - p.y);
+            Vector3::new(p.x + (other.x - p.x) * t, y, p.z + (other.z - p.z) * t)
+        };
+    }
+
+    None
+}
+
+impl Line {
+    /// The range of the steps of the line whose points round to a point in the
+    /// rectangle between `min` and `max`, both inclusive. The range errs on
+    /// the side of being a bit bigger.
+    pub fn steps_inside(&self, min: (f32, f32), max: (f32, f32)) -> Range<u64> {
+        let mut first = 0.0_f32;
+        let mut last = self.steps as f32 - 1.0;
+
+        let axes = [
+            (self.p0.x, self.step.x, min.0, max.0),
+            (self.p0.y, self.step.y, min.1, max.1),
+        ];
+        for &(start, step, min, max) in &axes {
+            let (lo, hi) = (min - 0.5, max + 0.5);
+
+            if step == 0.0 {
+                if start < lo || start > hi {
+                    return 0..0;
+                }
+                continue;
+            }
+
+            let (t0, t1) = ((lo - start) / step, (hi - start) / step);
+            first = first.max(t0.min(t1));
+            last = last.min(t0.max(t1));
+        }
+
+        if first > last {
+            return 0..0;
+        }
+
+        let first = (first.floor().max(0.0) as u64).saturating_sub(1);
+        let last = (last.ceil() as u64).saturating_add(1);
+
+        first..last.saturating_add(1).min(self.steps)
+    }
+}
+
 impl Iterator for Line {
     type Item = Vector3;
 
@@ -51,22 +151,45 @@ impl Iterator for Line {
 
         Some(p)
     }
+
+    // skipping the first points of the line must not step through them one by
+    // one, the line might be way longer than the part that is drawn.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let n = u64::try_from(n).unwrap_or(u64::MAX).min(self.steps);
+
+        self.p0 += self.step * n as f32;
+        self.steps -= n;
+
+        self.next()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Line, Vector3};
+    use super::{clip, Line, Vector3};
 
     #[test]
     fn test_point() {
         assert_eq!(
-            Line::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0))
-                .into_iter()
-                .collect::<Vec<_>>(),
+            Line::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0)).collect::<Vec<_>>(),
             vec![Vector3::new(0.0, 0.0, 0.0)]
         );
     }
 
+    #[test]
+    fn test_non_finite() {
+        let origin = Vector3::new(0.0, 0.0, 0.0);
+
+        for &p in &[
+            Vector3::new(f32::NAN, 0.0, 0.0),
+            Vector3::new(0.0, f32::INFINITY, 0.0),
+            Vector3::new(0.0, 0.0, f32::NEG_INFINITY),
+        ] {
+            assert_eq!(Line::new(origin, p).count(), 0);
+            assert_eq!(Line::new(p, origin).count(), 0);
+        }
+    }
+
     #[test]
     fn test_horizontal() {
         let mut pts = vec![
@@ -79,17 +202,13 @@ mod tests {
             Vector3::new(9.0, 5.0, 0.0),
         ];
         assert_eq!(
-            Line::new(Vector3::new(3.0, 5.0, 0.0), Vector3::new(9.0, 5.0, 0.0))
-                .into_iter()
-                .collect::<Vec<_>>(),
+            Line::new(Vector3::new(3.0, 5.0, 0.0), Vector3::new(9.0, 5.0, 0.0)).collect::<Vec<_>>(),
             pts
         );
 
         pts.reverse();
         assert_eq!(
-            Line::new(Vector3::new(9.0, 5.0, 0.0), Vector3::new(3.0, 5.0, 0.0))
-                .into_iter()
-                .collect::<Vec<_>>(),
+            Line::new(Vector3::new(9.0, 5.0, 0.0), Vector3::new(3.0, 5.0, 0.0)).collect::<Vec<_>>(),
             pts
         );
     }
@@ -105,21 +224,91 @@ mod tests {
             Vector3::new(7.0, 5.0, 0.0),
         ];
         assert_eq!(
-            Line::new(Vector3::new(7.0, 0.0, 0.0), Vector3::new(7.0, 5.0, 0.0))
-                .into_iter()
-                .collect::<Vec<_>>(),
+            Line::new(Vector3::new(7.0, 0.0, 0.0), Vector3::new(7.0, 5.0, 0.0)).collect::<Vec<_>>(),
             pts
         );
 
         pts.reverse();
         assert_eq!(
-            Line::new(Vector3::new(7.0, 5.0, 0.0), Vector3::new(7.0, 0.0, 0.0))
-                .into_iter()
-                .collect::<Vec<_>>(),
+            Line::new(Vector3::new(7.0, 5.0, 0.0), Vector3::new(7.0, 0.0, 0.0)).collect::<Vec<_>>(),
             pts
         );
     }
 
+    #[test]
+    fn test_steps_inside() {
+        let line = Line::new(
+            Vector3::new(-100.0, 0.0, 0.0),
+            Vector3::new(100.0, 50.0, 0.0),
+        );
+
+        let steps = line.steps_inside((0.0, 0.0), (9.0, 100.0));
+        assert_eq!(steps, 98..112);
+
+        let inside = Line::new(
+            Vector3::new(-100.0, 0.0, 0.0),
+            Vector3::new(100.0, 50.0, 0.0),
+        )
+        .enumerate()
+        .filter(|(_, p)| p.x.round() >= 0.0 && p.x.round() <= 9.0)
+        .map(|(i, _)| i as u64)
+        .collect::<Vec<_>>();
+        assert!(inside.iter().all(|i| steps.contains(i)));
+
+        assert_eq!(line.steps_inside((0.0, 60.0), (9.0, 100.0)), 0..0);
+    }
+
+    #[test]
+    fn test_clip() {
+        let (min, max) = ((0.0, 0.0), (10.0, 10.0));
+
+        assert_eq!(
+            clip(
+                Vector3::new(-1e30, 5.0, 0.0),
+                Vector3::new(1e30, 5.0, 2.0),
+                min,
+                max
+            ),
+            Some((Vector3::new(0.0, 5.0, 1.0), Vector3::new(10.0, 5.0, 1.0)))
+        );
+        assert_eq!(
+            clip(
+                Vector3::new(-10.0, 0.0, 0.0),
+                Vector3::new(20.0, 30.0, 0.0),
+                min,
+                max
+            ),
+            Some((Vector3::new(0.0, 10.0, 0.0), Vector3::new(0.0, 10.0, 0.0)))
+        );
+        assert_eq!(
+            clip(
+                Vector3::new(2.0, 2.0, 0.0),
+                Vector3::new(8.0, 3.0, 0.0),
+                min,
+                max
+            ),
+            Some((Vector3::new(2.0, 2.0, 0.0), Vector3::new(8.0, 3.0, 0.0)))
+        );
+        assert_eq!(
+            clip(
+                Vector3::new(-5.0, 20.0, 0.0),
+                Vector3::new(20.0, 20.0, 0.0),
+                min,
+                max
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_nth() {
+        let mut line = Line::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(10.0, 5.0, 0.0));
+
+        assert_eq!(line.nth(4), Some(Vector3::new(4.0, 2.0, 0.0)));
+        assert_eq!(line.next(), Some(Vector3::new(5.0, 2.5, 0.0)));
+        assert_eq!(line.nth(100), None);
+    }
+
     #[test]
     fn test_diagonal() {
         let mut pts = vec![
@@ -147,7 +336,6 @@ mod tests {
         ];
         assert_eq!(
             Line::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(20.0, 20.0, 20.0))
-                .into_iter()
                 .collect::<Vec<_>>(),
             pts
         );
@@ -155,7 +343,6 @@ mod tests {
         pts.reverse();
         assert_eq!(
             Line::new(Vector3::new(20.0, 20.0, 20.0), Vector3::new(0.0, 0.0, 0.0))
-                .into_iter()
                 .collect::<Vec<_>>(),
             pts
         );
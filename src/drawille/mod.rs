@@ -7,19 +7,33 @@
 //! gives the character that has a two dot vertical bar on the left!
 //!
 //! Since each character is a 4x2 matrix the user coordinates are remapped in
-//! 4x2 cells.
+//! 4x2 cells. The cells can also be rendered with other characters, see
+//! `Charset`.
 //!
 
+pub mod charset;
 pub mod line;
 
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 mod utils;
 use self::utils::btree_minmax;
 
-use crate::Vector3;
+use self::charset::Charset;
+use crate::{
+    frame::{self, Cell, Frame},
+    graphics::Image,
+    palette::{Color, Palette},
+    Vector3,
+};
 
 static BRAILLE_PATTERN_BLANK: char = '\u{2800}';
+
+// 4x4 Bayer matrix, a dot is lit when its brightness is above the threshold
+// at its position. Thresholds are spread so that any brightness lights dots
+// evenly across a 4x4 area.
+static BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
 static BRAILLE_OFFSET_MAP: [[u8; 2]; 4] = [
     [0x01, 0x08], // "⠁" , "⠈"
     [0x02, 0x10], // "⠂" , "⠐"
@@ -27,7 +41,9 @@ static BRAILLE_OFFSET_MAP: [[u8; 2]; 4] = [
     [0x40, 0x80], // "⡀" , "⢀"
 ];
 
-fn braille_offset_at(x: f32, y: f32) -> u8 {
+// position of the dot in the braille character at the given coordinates as
+// (row, column), see `BRAILLE_OFFSET_MAP`.
+fn braille_dot_at(x: f32, y: f32) -> (usize, usize) {
     let mut xoff = x.round() % 2.0;
     if xoff < 0.0 {
         xoff += 2.0;
@@ -38,38 +54,230 @@ fn braille_offset_at(x: f32, y: f32) -> u8 {
         yoff += 4.0;
     }
 
-    BRAILLE_OFFSET_MAP[yoff as usize][xoff as usize]
+    (yoff as usize, xoff as usize)
 }
 
+fn braille_offset_at(x: f32, y: f32) -> u8 {
+    let (r, c) = braille_dot_at(x, y);
+    BRAILLE_OFFSET_MAP[r][c]
+}
+
+// an unbounded Canvas only draws the dots whose coordinates are within this
+// limit by default, that is way bigger than any screen. See
+// `Canvas::set_max_dot`.
+const MAX_DOT: i32 = 1 << 12;
+
+// shade of the hidden dots of lines drawn with `HiddenLines::Dim`
+const DIM_SHADE: f32 = 0.2;
+
 #[derive(Debug)]
 pub struct Canvas {
+    // the pixels of an unbounded canvas, empty if there's a viewport
     rows: BTreeMap<i32, BTreeMap<i32, Pixel>>,
-    zrange: Option<(f32, f32)>,
+    viewport: Option<Viewport>,
+
+    palette: Palette,
+    charset: Charset,
+    painted_background: bool,
+    dither: bool,
+
+    // the limit of the coordinates of the dots of an unbounded canvas
+    max_dot: i32,
+}
+
+/// How to draw the dots of a line that are hidden by the dots or the
+/// occluders in front of them, see `Canvas::hidden_line`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HiddenLines {
+    /// Draw them like the visible ones.
+    Show,
+
+    /// Do not draw them at all.
+    Hide,
+
+    /// Draw them as dark as possible, only visible when rendering with
+    /// colors.
+    Dim,
+
+    /// Draw only some of them so that the line looks dashed.
+    Dash,
 }
 
-#[derive(Debug, PartialEq)]
+// The pixels of a Canvas of fixed size stored row by row, see
+// `Canvas::with_viewport`. The pixels can also be borrowed from the ones of a
+// bigger viewport, see `Canvas::par_fill_triangles`.
+#[derive(Debug)]
+struct Viewport<P = Vec<Pixel>> {
+    min_row: i32,
+    min_col: i32,
+    rows: usize,
+    cols: usize,
+    pixels: P,
+
+    // the dots lines are stepped through, which are the dots of the whole
+    // viewport even for the bands it's split in.
+    line_dots: (i32, i32, i32, i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 struct Pixel {
     // offset to sum to `BRAILLE_PATTERN_BLANK` to obtain the braille character
     // for a pixel
     braille_offset: u8,
 
-    // z of the pixel, the smaller the closer to the camera it is
-    z: f32,
+    // z of each dot laid out like `BRAILLE_OFFSET_MAP`, the smaller the closer
+    // to the camera it is. Dots that were never written are infinitely far.
+    depth: [[f32; 2]; 4],
+
+    // how much light each dot receives in the [0, 1] range, if the dot was
+    // shaded at all.
+    shade: [[Option<f32>; 2]; 4],
+}
+
+impl Pixel {
+    fn new() -> Self {
+        Pixel {
+            braille_offset: 0,
+            depth: [[f32::INFINITY; 2]; 4],
+            shade: [[None; 2]; 4],
+        }
+    }
+
+    // whether nothing was ever drawn on the pixel
+    fn is_empty(&self) -> bool {
+        self.braille_offset == 0 && self.depth.iter().flatten().all(|z| *z == f32::INFINITY)
+    }
+
+    // the values of the visible dots among the ones in `mask` only
+    fn visible<'s, T>(
+        &'s self,
+        mask: u8,
+        values: &'s [[T; 2]; 4],
+    ) -> impl Iterator<Item = &'s T> + 's {
+        BRAILLE_OFFSET_MAP
+            .iter()
+            .zip(values.iter())
+            .flat_map(|(offsets, values)| offsets.iter().zip(values.iter()))
+            .filter(move |(&offset, _)| self.braille_offset & mask & offset != 0)
+            .map(|(_, v)| v)
+    }
+
+    // z of the dots in `mask` which is the average z of the visible ones, use
+    // 0xFF for the whole pixel.
+    fn z(&self, mask: u8) -> f32 {
+        mean(self.visible(mask, &self.depth).cloned()).unwrap_or(f32::INFINITY)
+    }
+
+    // shade of the dots in `mask` which is the average shade of the visible
+    // and shaded ones, use 0xFF for the whole pixel.
+    fn shade(&self, mask: u8) -> Option<f32> {
+        mean(self.visible(mask, &self.shade).filter_map(|&s| s))
+    }
+
+    // Brightness in [0, 1] of the dots in `mask` given the range of z of the
+    // whole canvas. Shaded dots are brighter the more light they receive, the
+    // others are brighter the closer they are to the camera to simulate depth.
+    fn brightness(&self, mask: u8, (zmin, zmax): (f32, f32)) -> f32 {
+        match self.shade(mask) {
+            Some(shade) => shade,
+            None => {
+                if zmax - zmin != 0.0 {
+                    1.0 - (self.z(mask) - zmin) / (zmax - zmin)
+                } else {
+                    // if there's only a z value then it's always at the top
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, n) = values.fold((0.0, 0_u8), |(sum, n), v| (sum + v, n + 1));
+
+    if n == 0 {
+        None
+    } else {
+        Some(sum / f32::from(n))
+    }
 }
 
 impl Canvas {
-    /// The optimal background color for rendering the Canvas
-    pub fn background_color() -> impl termion::color::Color {
-        termion::color::AnsiValue::grayscale(3)
+    /// The optimal background color for rendering the Canvas with its
+    /// palette.
+    pub fn background_color(&self) -> Color {
+        self.palette.background()
     }
 
     pub fn new() -> Self {
         Canvas {
             rows: BTreeMap::new(),
-            zrange: None,
+            viewport: None,
+            palette: Palette::default(),
+            charset: Charset::Braille,
+            painted_background: false,
+            dither: false,
+            max_dot: MAX_DOT,
+        }
+    }
+
+    /// Create a Canvas that only keeps the cells between the given rows and
+    /// columns in canvas space, both inclusive, and clips everything else.
+    /// The cells are allocated upfront, so drawing on it is much faster than
+    /// on an unbounded Canvas.
+    pub fn with_viewport(min_row: i32, max_row: i32, min_col: i32, max_col: i32) -> Self {
+        let rows = (max_row - min_row + 1).max(0) as usize;
+        let cols = (max_col - min_col + 1).max(0) as usize;
+
+        let mut viewport = Viewport {
+            min_row,
+            min_col,
+            rows,
+            cols,
+            pixels: vec![Pixel::new(); rows * cols],
+            line_dots: (0, 0, 0, 0),
+        };
+        viewport.line_dots = viewport.dots();
+
+        Canvas {
+            viewport: Some(viewport),
+            ..Canvas::new()
         }
     }
 
+    /// Set the palette used to color the pixels by their shade or depth when
+    /// rendering with colors.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Set the characters used to render the cells, braille by default.
+    pub fn set_charset(&mut self, charset: Charset) {
+        self.charset = charset;
+    }
+
+    /// Light only some of the dots of the pixels according to their
+    /// brightness, so that shading is visible as dot density even without
+    /// colors. The dots that are not lit still hide the ones behind them.
+    pub fn set_dither(&mut self, dither: bool) {
+        self.dither = dither;
+    }
+
+    /// Only draw the dots whose coordinates are between `-max_dot` and
+    /// `max_dot` on an unbounded Canvas. The default is way bigger than any
+    /// screen, but drawing a triangle as big as that takes a while, therefore
+    /// it's better to set it to the size of the output when it's known.
+    pub fn set_max_dot(&mut self, max_dot: i32) {
+        self.max_dot = max_dot.max(0);
+    }
+
+    /// Tell whether the rows are going to be drawn over `background_color`
+    /// instead of the default background of the terminal. Characters that
+    /// need to change the background color restore it afterwards.
+    pub fn set_painted_background(&mut self, painted: bool) {
+        self.painted_background = painted;
+    }
+
     // convert coordinates from user space to canvas space
     pub fn pos(x: f32, y: f32) -> (i32, i32) {
         (
@@ -78,13 +286,18 @@ impl Canvas {
         )
     }
 
-    pub fn rows(&self, with_colors: bool) -> Rows {
-        let (min_row, max_row, min_col, _) =
-            self.dimensions().unwrap_or((i32::max_value(), 0, 0, 0));
+    pub fn rows(&self, with_colors: bool) -> Rows<'_> {
+        let (min_row, max_row, min_col, _) = self.dimensions().unwrap_or((i32::MAX, 0, 0, 0));
 
         self.frame(with_colors, min_row, max_row, min_col, None)
     }
 
+    /// Like `rows`, but as a Frame whose cells can be inspected before
+    /// serializing them.
+    pub fn to_frame(&self, with_colors: bool) -> Frame {
+        self.rows(with_colors).into_frame()
+    }
+
     /// bounds are in canvas space, use `pos` to perform the conversion if
     /// needed.
     ///
@@ -99,9 +312,16 @@ impl Canvas {
         max_row: i32,
         min_col: i32,
         max_col: Option<i32>,
-    ) -> Rows {
+    ) -> Rows<'_> {
         Rows {
             canvas: self,
+            // the ascii ramp and dithering also depend on the brightness of
+            // the pixels
+            zrange: if with_colors || self.charset == Charset::Ascii || self.dither {
+                self.zrange()
+            } else {
+                None
+            },
             min_row,
             max_row,
             min_col,
@@ -110,16 +330,80 @@ impl Canvas {
         }
     }
 
+    /// Render the dots in the given area in user space as the pixels of an
+    /// Image. Without colors all the dots get the brightest color of the
+    /// palette.
+    pub fn image(
+        &self,
+        with_colors: bool,
+        min_x: i32,
+        min_y: i32,
+        width: usize,
+        height: usize,
+    ) -> Image {
+        let mut image = Image::new(width, height);
+        if width == 0 || height == 0 {
+            return image;
+        }
+
+        let zrange = if with_colors { self.zrange() } else { None };
+
+        let (min_col, min_row) = Canvas::pos(min_x as f32, min_y as f32);
+        let (max_col, max_row) = Canvas::pos(
+            (min_x + width as i32 - 1) as f32,
+            (min_y + height as i32 - 1) as f32,
+        );
+
+        for row in min_row..=max_row {
+            for col in min_col..=max_col {
+                let pix = match self.pixel(row, col) {
+                    None => continue,
+                    Some(pix) => pix,
+                };
+
+                for (r, offsets) in BRAILLE_OFFSET_MAP.iter().enumerate() {
+                    for (c, &offset) in offsets.iter().enumerate() {
+                        let x = col * 2 + c as i32 - min_x;
+                        let y = row * 4 + r as i32 - min_y;
+
+                        if pix.braille_offset & offset == 0
+                            || x < 0
+                            || y < 0
+                            || x as usize >= width
+                            || y as usize >= height
+                        {
+                            continue;
+                        }
+
+                        let t = zrange.map_or(1.0, |zrange| pix.brightness(offset, zrange));
+                        image.set(x as usize, y as usize, Some(self.palette.color(t).rgb()));
+                    }
+                }
+            }
+        }
+
+        image
+    }
+
     // Get a tuple with the minimum row, maximum row, minimum column and maximum
     // column values in canvas space.
     pub fn dimensions(&self) -> Option<(i32, i32, i32, i32)> {
+        if self.viewport.is_some() {
+            return self.pixels().fold(None, |dims, ((r, c), _)| match dims {
+                None => Some((r, r, c, c)),
+                Some((min_r, max_r, min_c, max_c)) => {
+                    Some((min_r.min(r), max_r.max(r), min_c.min(c), max_c.max(c)))
+                }
+            });
+        }
+
         btree_minmax(&self.rows).map(|(&min_row, &max_row)| {
             let (min_c, max_c) = self
                 .rows
                 .values()
                 .map(|r| btree_minmax(r).unwrap_or((&0, &0)))
                 .fold(
-                    (i32::max_value(), i32::min_value()),
+                    (i32::MAX, i32::MIN),
                     |(min_c, max_c), (row_min_c, row_max_c)| {
                         (min_c.min(*row_min_c), max_c.max(*row_max_c))
                     },
@@ -129,104 +413,558 @@ impl Canvas {
         })
     }
 
+    // minimum and maximum z of the visible pixels
+    fn zrange(&self) -> Option<(f32, f32)> {
+        self.pixels()
+            .map(|(_, pix)| pix.z(0xFF))
+            .filter(|z| z.is_finite())
+            .fold(None, |range, z| match range {
+                None => Some((z, z)),
+                Some((minz, maxz)) => Some((z.min(minz), z.max(maxz))),
+            })
+    }
+
     pub fn clear(&mut self) {
         self.rows.clear();
+
+        if let Some(viewport) = &mut self.viewport {
+            for pix in &mut viewport.pixels {
+                *pix = Pixel::new();
+            }
+        }
+    }
+
+    // the pixel at the given position in canvas space, if anything was drawn
+    // there.
+    fn pixel(&self, row: i32, col: i32) -> Option<&Pixel> {
+        match &self.viewport {
+            None => self.rows.get(&row).and_then(|r| r.get(&col)),
+            Some(viewport) => viewport
+                .index(row, col)
+                .map(|i| &viewport.pixels[i])
+                .filter(|pix| !pix.is_empty()),
+        }
+    }
+
+    // all the pixels something was drawn on along with their position as
+    // (row, column).
+    fn pixels(&self) -> Box<dyn Iterator<Item = ((i32, i32), &Pixel)> + '_> {
+        match &self.viewport {
+            None => Box::new(
+                self.rows
+                    .iter()
+                    .flat_map(|(&r, row)| row.iter().map(move |(&c, pix)| ((r, c), pix))),
+            ),
+            Some(viewport) => Box::new(
+                viewport
+                    .pixels
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pix)| !pix.is_empty())
+                    .map(move |(i, pix)| (viewport.position(i), pix)),
+            ),
+        }
+    }
+
+    // minimum and maximum column of the pixels in the given row
+    fn row_bounds(&self, row: i32) -> Option<(i32, i32)> {
+        match &self.viewport {
+            None => self
+                .rows
+                .get(&row)
+                .and_then(btree_minmax)
+                .map(|(&min_c, &max_c)| (min_c, max_c)),
+            Some(viewport) => {
+                let cols = viewport.min_col..viewport.min_col + viewport.cols as i32;
+                let mut set = cols.filter(|&c| self.pixel(row, c).is_some());
+
+                set.next()
+                    .map(|min_c| (min_c, set.next_back().unwrap_or(min_c)))
+            }
+        }
     }
 
     pub fn line(&mut self, p0: Vector3, p1: Vector3) {
-        for p in line::Line::new(p0.round(), p1.round()) {
+        for (_, p) in line_points(self.line_dots(), p0, p1) {
             self.set(p);
         }
     }
 
+    /// Turn on the dot at the given point unless there's already a dot that is
+    /// closer to the camera, that is with a smaller z.
     pub fn set(&mut self, p: Vector3) {
-        use std::collections::btree_map::Entry;
+        self.set_shaded(p, None);
+    }
 
-        let (c, r) = Self::pos(p.x, p.y);
+    /// Like `set`, but also record how much light the dot receives. When
+    /// rendering with colors, pixels with shaded dots are colored by their
+    /// shade instead of their depth.
+    pub fn set_shaded(&mut self, p: Vector3, shade: Option<f32>) {
+        self.set_dot(p, shade);
+    }
 
-        self.zrange = match self.zrange {
-            None => Some((p.z, p.z)),
-            Some((minz, maxz)) => Some((minz.min(p.z), maxz.max(p.z))),
-        };
+    // Turn on a dot that is hidden by the one at the same position unless
+    // it's visible already. The depth is left untouched so that the dot keeps
+    // hiding what's behind it.
+    fn set_hidden(&mut self, p: Vector3, shade: Option<f32>) {
+        let (c, r) = Self::pos(p.x, p.y);
+        let (dot_r, dot_c) = braille_dot_at(p.x, p.y);
+        let offset = BRAILLE_OFFSET_MAP[dot_r][dot_c];
 
-        let braille_offset = braille_offset_at(p.x, p.y);
+        // only dots that were drawn before can hide anything
+        if self.pixel(r, c).is_none() {
+            return;
+        }
 
-        match self.rows.entry(r).or_default().entry(c) {
-            Entry::Vacant(v) => {
-                v.insert(Pixel {
-                    braille_offset,
-                    z: p.z,
-                });
+        if let Some(pix) = self.pixel_mut(r, c) {
+            if pix.braille_offset & offset == 0 {
+                pix.shade[dot_r][dot_c] = shade;
+                pix.braille_offset |= offset;
             }
-            Entry::Occupied(mut o) => {
-                let pix = o.get_mut();
+        }
+    }
 
-                pix.braille_offset |= braille_offset;
-                pix.z = pix.z.min(p.z);
-            }
-        };
+    // whether there's a dot closer to the camera than the given point, be it
+    // visible or not.
+    fn is_hidden(&self, p: Vector3) -> bool {
+        let (c, r) = Self::pos(p.x, p.y);
+        let (dot_r, dot_c) = braille_dot_at(p.x, p.y);
+
+        self.pixel(r, c)
+            .is_some_and(|pix| p.z >= pix.depth[dot_r][dot_c])
     }
 
     pub fn is_set(&self, x: f32, y: f32) -> bool {
         let dot_index = braille_offset_at(x, y);
         let (x, y) = Self::pos(x, y);
 
-        self.rows
-            .get(&y)
-            .and_then(|row| row.get(&x))
-            .map_or(false, |c| c.braille_offset & dot_index != 0)
+        self.pixel(y, x)
+            .is_some_and(|c| c.braille_offset & dot_index != 0)
     }
 
     pub fn triangle(&mut self, p0: Vector3, p1: Vector3, p2: Vector3) {
-        let midz = (p0.z + p1.z + p2.z) / 3.0;
-        self.triangle_line(p0, p1, midz);
-        self.triangle_line(p0, p2, midz);
-        self.triangle_line(p1, p2, midz);
+        self.triangle_outline(p0, p1, p2, None);
     }
 
-    pub fn fill_triangle(&mut self, mut p0: Vector3, mut p1: Vector3, mut p2: Vector3) {
-        // TODO: speed this up
+    /// Fill the given triangle setting all the dots whose center is inside it.
+    /// Dots exactly on an edge are set only if the edge is a top or left edge
+    /// so that triangles sharing an edge never overlap. The z of each dot is
+    /// interpolated from the z of the vertices.
+    pub fn fill_triangle(&mut self, p0: Vector3, p1: Vector3, p2: Vector3) {
+        self.rasterize(p0, p1, p2, None, true);
+    }
 
-        // ensure p0 is the point with highest y, then comes p1 and then p2
-        if p1.y < p0.y {
-            std::mem::swap(&mut p0, &mut p1);
+    /// Like `fill_triangle`, but also shade the dots interpolating the shades
+    /// of the vertices. Use the same shade for all the vertices for flat
+    /// shading.
+    pub fn fill_triangle_shaded(
+        &mut self,
+        p0: Vector3,
+        p1: Vector3,
+        p2: Vector3,
+        shades: [f32; 3],
+    ) {
+        self.rasterize(p0, p1, p2, Some(shades), true);
+    }
+
+    /// Fill all the given triangles in order, each with the shades of its
+    /// vertices if any, like calling `fill_triangle` or
+    /// `fill_triangle_shaded` on each of them.
+    ///
+    /// With the `rayon` feature the rows of a Canvas with a viewport are
+    /// split in bands that are filled in parallel, the dots are exactly the
+    /// same as filling the triangles one after the other.
+    pub fn fill_triangles(&mut self, triangles: &[([Vector3; 3], Option<[f32; 3]>)]) {
+        #[cfg(feature = "rayon")]
+        {
+            if self.viewport.is_some() && rayon::current_num_threads() > 1 {
+                self.par_fill_triangles(triangles);
+                return;
+            }
         }
-        if p2.y < p0.y {
-            std::mem::swap(&mut p0, &mut p2);
+
+        for &([p0, p1, p2], shades) in triangles {
+            self.rasterize(p0, p1, p2, shades, true);
         }
-        if p2.y < p1.y {
-            std::mem::swap(&mut p1, &mut p2);
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_fill_triangles(&mut self, triangles: &[([Vector3; 3], Option<[f32; 3]>)]) {
+        use rayon::prelude::*;
+
+        let viewport = match &mut self.viewport {
+            None => return,
+            Some(viewport) => viewport,
+        };
+
+        if viewport.cols == 0 {
+            return;
         }
 
-        let midz = (p0.z + p1.z + p2.z) / 3.0;
+        // more bands than threads so that the ones with few triangles don't
+        // leave threads idle
+        let bands = rayon::current_num_threads() * 4;
+        let band_rows = viewport.rows.div_ceil(bands).max(1);
+
+        let (min_row, min_col, cols) = (viewport.min_row, viewport.min_col, viewport.cols);
+        let line_dots = viewport.line_dots;
+        viewport
+            .pixels
+            .par_chunks_mut(cols * band_rows)
+            .enumerate()
+            .for_each(|(i, pixels)| {
+                let mut band = Viewport {
+                    min_row: min_row + (i * band_rows) as i32,
+                    min_col,
+                    rows: pixels.len() / cols,
+                    cols,
+                    pixels,
+                    line_dots,
+                };
+
+                for &([p0, p1, p2], shades) in triangles {
+                    band.rasterize(p0, p1, p2, shades, true);
+                }
+            });
+    }
+
+    /// Hide the dots behind the given triangle without drawing it. Lines
+    /// drawn afterwards with `hidden_line` know which of their dots are
+    /// hidden.
+    pub fn occlude(&mut self, p0: Vector3, p1: Vector3, p2: Vector3) {
+        self.rasterize(p0, p1, p2, None, false);
+    }
+
+    /// Draw a line whose dots that are hidden by closer ones, including the
+    /// occluders, are drawn according to `hidden`.
+    pub fn hidden_line(&mut self, p0: Vector3, p1: Vector3, hidden: HiddenLines) {
+        for (i, p) in line_points(self.line_dots(), p0, p1) {
+            if !self.is_hidden(p) {
+                self.set(p);
+                continue;
+            }
+
+            match hidden {
+                HiddenLines::Show => self.set_hidden(p, None),
+                HiddenLines::Hide => {}
+                HiddenLines::Dim => self.set_hidden(p, Some(DIM_SHADE)),
+                HiddenLines::Dash => {
+                    if i % 4 < 2 {
+                        self.set_hidden(p, None);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Something the dots of triangles and lines can be drawn on, that is a Canvas
+// or a band of the rows of its viewport, see `Canvas::par_fill_triangles`.
+trait Raster {
+    // The dots that can be drawn as (min x, min y, max x, max y), all
+    // inclusive. Even an unbounded Canvas has a limit so that huge coordinates
+    // cannot overflow nor take forever to draw.
+    fn clip_dots(&self) -> (i32, i32, i32, i32);
+
+    // The dots lines are stepped through, usually the same as `clip_dots`.
+    // The bands of a viewport step through the dots of the whole viewport
+    // so that lines go through the exact same points on all of them.
+    fn line_dots(&self) -> (i32, i32, i32, i32);
+
+    // the pixel to draw on at the given position in canvas space, `None` if
+    // it cannot be drawn on.
+    fn pixel_mut(&mut self, row: i32, col: i32) -> Option<&mut Pixel>;
+
+    // whether the given point is a dot that can be drawn
+    fn can_draw(&self, p: Vector3) -> bool {
+        let (min_x, min_y, max_x, max_y) = self.clip_dots();
+        let (x, y) = (p.x.round(), p.y.round());
+
+        !p.z.is_nan()
+            && x >= min_x as f32
+            && x <= max_x as f32
+            && y >= min_y as f32
+            && y <= max_y as f32
+    }
+
+    // see `Canvas::set_shaded`
+    fn set_dot(&mut self, p: Vector3, shade: Option<f32>) {
+        if !self.can_draw(p) {
+            return;
+        }
+
+        let (c, r) = Canvas::pos(p.x, p.y);
+        let (dot_r, dot_c) = braille_dot_at(p.x, p.y);
+
+        let pix = match self.pixel_mut(r, c) {
+            None => return,
+            Some(pix) => pix,
+        };
+
+        if p.z >= pix.depth[dot_r][dot_c] {
+            return;
+        }
+
+        pix.depth[dot_r][dot_c] = p.z;
+        pix.shade[dot_r][dot_c] = shade;
+        pix.braille_offset |= BRAILLE_OFFSET_MAP[dot_r][dot_c];
+    }
+
+    // Like `set_dot`, but the dot is turned off instead so that it hides the dots
+    // behind it without being visible itself.
+    fn occlude_dot(&mut self, p: Vector3) {
+        if !self.can_draw(p) {
+            return;
+        }
+
+        let (c, r) = Canvas::pos(p.x, p.y);
+        let (dot_r, dot_c) = braille_dot_at(p.x, p.y);
+
+        let pix = match self.pixel_mut(r, c) {
+            None => return,
+            Some(pix) => pix,
+        };
+
+        if p.z >= pix.depth[dot_r][dot_c] {
+            return;
+        }
+
+        pix.depth[dot_r][dot_c] = p.z;
+        pix.shade[dot_r][dot_c] = None;
+        pix.braille_offset &= !BRAILLE_OFFSET_MAP[dot_r][dot_c];
+    }
+
+    fn rasterize(
+        &mut self,
+        p0: Vector3,
+        p1: Vector3,
+        p2: Vector3,
+        shades: Option<[f32; 3]>,
+        visible: bool,
+    ) {
+        if !(p0.is_finite() && p1.is_finite() && p2.is_finite()) {
+            return;
+        }
+
+        let area = edge_function(p0, p1, p2);
+
+        // degenerate triangles have no inside, draw them as lines so that they
+        // don't disappear. They cannot hide anything though.
+        if area == 0.0 {
+            if !visible {
+                return;
+            }
+
+            let shade = shades.map(|[s0, s1, s2]| (s0 + s1 + s2) / 3.0);
+            self.triangle_outline(p0, p1, p2, shade);
+            return;
+        }
+
+        // make sure the vertices are always in the same winding order
+        let (p1, p2, area, shades) = if area < 0.0 {
+            (p2, p1, -area, shades.map(|[s0, s1, s2]| [s0, s2, s1]))
+        } else {
+            (p1, p2, area, shades)
+        };
+
+        let edges = [(p1, p2), (p2, p0), (p0, p1)];
+        let top_left = [
+            is_top_left(p1, p2),
+            is_top_left(p2, p0),
+            is_top_left(p0, p1),
+        ];
+
+        let bbox = (
+            p0.x.min(p1.x).min(p2.x).ceil() as i32,
+            p0.y.min(p1.y).min(p2.y).ceil() as i32,
+            p0.x.max(p1.x).max(p2.x).floor() as i32,
+            p0.y.max(p1.y).max(p2.y).floor() as i32,
+        );
+
+        // the weights of the vertices at the given dot, `None` if the dot is
+        // outside of the triangle
+        let weights = |x: i32, y: i32| {
+            let p = Vector3::new(x as f32, y as f32, 0.0);
+
+            let mut w = [0.0; 3];
+            for (i, &(a, b)) in edges.iter().enumerate() {
+                w[i] = edge_function(a, b, p);
+                if !(w[i] > 0.0 || (w[i] == 0.0 && top_left[i])) {
+                    return None;
+                }
+            }
+
+            Some(w)
+        };
+
+        // don't bother with the dots that would be clipped anyway
+        let (min_x, min_y, max_x, max_y) = intersect_dots(bbox, self.clip_dots());
 
-        for (line_start, line_end) in line::Line::new(p0, p1).zip(line::Line::new(p0, p2)) {
-            self.triangle_line(line_start, line_end, midz);
+        let mut filled = false;
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let w = match weights(x, y) {
+                    None => continue,
+                    Some(w) => w,
+                };
+
+                filled = true;
+
+                let z = (w[0] * p0.z + w[1] * p1.z + w[2] * p2.z) / area;
+                let p = Vector3::new(x as f32, y as f32, z);
+                if !visible {
+                    self.occlude_dot(p);
+                    continue;
+                }
+
+                let shade = shades.map(|s| (w[0] * s[0] + w[1] * s[1] + w[2] * s[2]) / area);
+                self.set_dot(p, shade);
+            }
         }
 
-        for (line_start, line_end) in line::Line::new(p2, p0).zip(line::Line::new(p2, p1)) {
-            self.triangle_line(line_start, line_end, midz);
+        if filled || !visible {
+            return;
         }
 
-        for (line_start, line_end) in line::Line::new(p1, p0).zip(line::Line::new(p1, p2)) {
-            self.triangle_line(line_start, line_end, midz);
+        // Triangles too small or too thin to contain the center of any dot are
+        // drawn as their outline so that they don't disappear. The bands of a
+        // viewport look for such dots on the whole viewport so that they all
+        // agree on whether the outline is drawn.
+        let line_dots = self.line_dots();
+        if line_dots != self.clip_dots() {
+            let (min_x, min_y, max_x, max_y) = intersect_dots(bbox, line_dots);
+            let mut dots = (min_y..=max_y).flat_map(|y| (min_x..=max_x).map(move |x| (x, y)));
+            if dots.any(|(x, y)| weights(x, y).is_some()) {
+                return;
+            }
         }
 
-        self.triangle(p0, p1, p2);
+        let shade = shades.map(|[s0, s1, s2]| (s0 + s1 + s2) / 3.0);
+        self.triangle_outline(p0, p1, p2, shade);
+    }
+
+    fn triangle_outline(&mut self, p0: Vector3, p1: Vector3, p2: Vector3, shade: Option<f32>) {
+        if !(p0.is_finite() && p1.is_finite() && p2.is_finite()) {
+            return;
+        }
+
+        let midz = (p0.z + p1.z + p2.z) / 3.0;
+        self.triangle_line(p0, p1, midz, shade);
+        self.triangle_line(p0, p2, midz, shade);
+        self.triangle_line(p1, p2, midz, shade);
     }
 
     // lines for triangles all have the same z for flat shading
-    fn triangle_line(&mut self, p0: Vector3, p1: Vector3, z: f32) {
-        for mut p in line::Line::new(p0.round(), p1.round()) {
+    fn triangle_line(&mut self, p0: Vector3, p1: Vector3, z: f32, shade: Option<f32>) {
+        for (_, mut p) in line_points(self.line_dots(), p0, p1) {
             p.z = z;
-            self.set(p);
+            self.set_dot(p, shade);
         }
     }
 }
 
+impl Raster for Canvas {
+    fn clip_dots(&self) -> (i32, i32, i32, i32) {
+        match &self.viewport {
+            None => (-self.max_dot, -self.max_dot, self.max_dot, self.max_dot),
+            Some(viewport) => viewport.dots(),
+        }
+    }
+
+    fn line_dots(&self) -> (i32, i32, i32, i32) {
+        match &self.viewport {
+            None => self.clip_dots(),
+            Some(viewport) => viewport.line_dots,
+        }
+    }
+
+    fn pixel_mut(&mut self, row: i32, col: i32) -> Option<&mut Pixel> {
+        match &mut self.viewport {
+            None => Some(
+                self.rows
+                    .entry(row)
+                    .or_default()
+                    .entry(col)
+                    .or_insert_with(Pixel::new),
+            ),
+            Some(viewport) => viewport
+                .index(row, col)
+                .map(move |i| &mut viewport.pixels[i]),
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Raster for Viewport<&mut [Pixel]> {
+    fn clip_dots(&self) -> (i32, i32, i32, i32) {
+        self.dots()
+    }
+
+    fn line_dots(&self) -> (i32, i32, i32, i32) {
+        self.line_dots
+    }
+
+    fn pixel_mut(&mut self, row: i32, col: i32) -> Option<&mut Pixel> {
+        self.index(row, col).map(move |i| &mut self.pixels[i])
+    }
+}
+
+// the dots that are in both of the given ranges of dots, see `Raster::clip_dots`
+fn intersect_dots(a: (i32, i32, i32, i32), b: (i32, i32, i32, i32)) -> (i32, i32, i32, i32) {
+    (a.0.max(b.0), a.1.max(b.1), a.2.min(b.2), a.3.min(b.3))
+}
+
+// The points of the line between the given points along with their index in
+// the line stepping through the given dots, see `Raster::line_dots`. The ones
+// that cannot be drawn are skipped without ever stepping through them.
+fn line_points(
+    (min_x, min_y, max_x, max_y): (i32, i32, i32, i32),
+    p0: Vector3,
+    p1: Vector3,
+) -> impl Iterator<Item = (usize, Vector3)> {
+    let (min, max) = ((min_x as f32, min_y as f32), (max_x as f32, max_y as f32));
+
+    // stepping from a point way past the dots that can be drawn loses too
+    // much precision, move it closer first. Lines that are only a bit
+    // longer are left alone so that their dots don't depend on where they
+    // are clipped.
+    let margin = MAX_DOT as f32;
+    let line = line::clip(
+        p0,
+        p1,
+        (min.0 - margin, min.1 - margin),
+        (max.0 + margin, max.1 + margin),
+    );
+
+    line.into_iter().flat_map(move |(p0, p1)| {
+        let line = line::Line::new(p0.round(), p1.round());
+        let steps = line.steps_inside(min, max);
+
+        let to_usize = |n| usize::try_from(n).unwrap_or(usize::MAX);
+        line.enumerate()
+            .skip(to_usize(steps.start))
+            .take(to_usize(steps.end - steps.start))
+    })
+}
+
+// Twice the signed area of the triangle `a`, `b`, `p`. It's positive when `p`
+// is on the right of the edge going from `a` to `b`, remember that the Y axis
+// points down.
+fn edge_function(a: Vector3, b: Vector3, p: Vector3) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// Whether the edge from `a` to `b` is a top or a left edge of a triangle whose
+// vertices are sorted so that `edge_function` is positive inside it.
+fn is_top_left(a: Vector3, b: Vector3) -> bool {
+    let d = b - a;
+    (d.y == 0.0 && d.x > 0.0) || d.y < 0.0
+}
+
 #[derive(Debug)]
 pub struct Rows<'a> {
     canvas: &'a Canvas,
+    zrange: Option<(f32, f32)>,
     with_colors: bool,
     min_row: i32,
     max_row: i32,
@@ -235,32 +973,136 @@ pub struct Rows<'a> {
 }
 
 impl<'a> Rows<'a> {
-    fn braille(&self, pix: &Pixel) -> String {
-        let c = std::char::from_u32(BRAILLE_PATTERN_BLANK as u32 + u32::from(pix.braille_offset))
-            .unwrap();
+    /// Collect the remaining rows in a Frame, so that each character comes
+    /// with its colors instead of the escape sequences to set them.
+    pub fn into_frame(mut self) -> Frame {
+        let background = self.background();
+
+        Frame {
+            rows: std::iter::from_fn(|| self.next_cells()).collect(),
+            background,
+        }
+    }
 
-        if !self.with_colors {
-            return c.to_string();
+    fn next_cells(&mut self) -> Option<Vec<Cell>> {
+        if self.min_row > self.max_row {
+            return None;
         }
 
-        let (zmin, zmax) = self.canvas.zrange.unwrap();
+        let blank = Cell::new(self.canvas.charset.blank());
+        let row = match self.canvas.row_bounds(self.min_row) {
+            None => vec![],
+            Some((_, row_max_c)) => (self.min_col..=self.max_col.unwrap_or(row_max_c))
+                .map(|x| {
+                    self.canvas
+                        .pixel(self.min_row, x)
+                        .map_or(blank, |pix| self.cell(pix, self.min_row, x))
+                })
+                .collect(),
+        };
 
-        // Use the ANSI grayscales as a form of alpha channel to simulate depth.
-        // The first shades of black are not taken into account because they're
-        // too bright which makes the eyes think the pixel is closer even though
-        // it's not.
-        let gray = if zmax - zmin != 0.0 {
-            23 - (((pix.z - zmin) / (zmax - zmin) * 19.0).round()) as u8
+        self.min_row += 1;
+
+        Some(row)
+    }
+
+    fn cell(&self, pix: &Pixel, row: i32, col: i32) -> Cell {
+        let charset = self.canvas.charset;
+        let subpixels = charset.subpixels();
+
+        let offset = if self.canvas.dither {
+            self.dither(pix, row, col)
         } else {
-            // if there's only a z value then it's always at the top
-            23
+            pix.braille_offset
         };
+        if offset == 0 {
+            return Cell::new(charset.blank());
+        }
 
-        format!(
-            "{}{}",
-            termion::color::Fg(termion::color::AnsiValue::grayscale(gray)),
-            c
-        )
+        let depth = Some(pix.z(offset)).filter(|z| z.is_finite());
+
+        // a sub pixel is set as soon as any of its dots is
+        let bits = subpixels
+            .iter()
+            .enumerate()
+            .filter(|(_, &mask)| offset & mask != 0)
+            .fold(0, |bits, (i, _)| bits | 1 << i);
+
+        if charset == Charset::HalfBlock && self.with_colors && bits == 0b11 {
+            // draw the lower half with the background color so that each half
+            // gets its own color
+            let (top, bottom) = (
+                pix.brightness(subpixels[0], self.brightness_zrange()),
+                pix.brightness(subpixels[1], self.brightness_zrange()),
+            );
+
+            return Cell {
+                glyph: charset.glyph(0b01, 0.0),
+                depth,
+                fg: Some(self.canvas.palette.color(top)),
+                bg: Some(self.canvas.palette.color(bottom)),
+            };
+        }
+
+        let t = self
+            .zrange
+            .map_or(1.0, |zrange| pix.brightness(0xFF, zrange));
+        let density = offset.count_ones() as f32 / 8.0 * t;
+        let c = charset.glyph(bits, density);
+
+        let fg = if self.with_colors {
+            Some(self.canvas.palette.color(t))
+        } else {
+            None
+        };
+
+        Cell {
+            glyph: c,
+            depth,
+            fg,
+            bg: None,
+        }
+    }
+
+    // the visible dots of the pixel at the given position that are bright
+    // enough to be lit according to `BAYER_MATRIX`.
+    fn dither(&self, pix: &Pixel, row: i32, col: i32) -> u8 {
+        let zrange = self.brightness_zrange();
+        let mut offset = 0;
+
+        for (r, offsets) in BRAILLE_OFFSET_MAP.iter().enumerate() {
+            for (c, &dot) in offsets.iter().enumerate() {
+                if pix.braille_offset & dot == 0 {
+                    continue;
+                }
+
+                let x = (col * 2 + c as i32).rem_euclid(4) as usize;
+                let y = (row * 4 + r as i32).rem_euclid(4) as usize;
+                let threshold = (f32::from(BAYER_MATRIX[y][x]) + 0.5) / 16.0;
+
+                if pix.brightness(dot, zrange) > threshold {
+                    offset |= dot;
+                }
+            }
+        }
+
+        offset
+    }
+
+    // The range of z to compute the brightness of the pixels. There's none
+    // when nothing visible has a finite z, for example when only occluders
+    // were drawn, and then the unshaded dots are as bright as possible.
+    fn brightness_zrange(&self) -> (f32, f32) {
+        self.zrange.unwrap_or((0.0, 0.0))
+    }
+
+    // the background the cells with their own background color restore
+    fn background(&self) -> Option<Color> {
+        if self.canvas.painted_background {
+            Some(self.canvas.background_color())
+        } else {
+            None
+        }
     }
 }
 
@@ -268,29 +1110,68 @@ impl<'a> Iterator for Rows<'a> {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.min_row > self.max_row {
-            return None;
+        let background = self.background();
+        self.next_cells()
+            .map(|cells| frame::ansi_row(&cells, background))
+    }
+}
+
+impl<P> Viewport<P> {
+    // index of the pixel at the given position, if it's inside
+    fn index(&self, row: i32, col: i32) -> Option<usize> {
+        let r = usize::try_from(row.checked_sub(self.min_row)?).ok()?;
+        let c = usize::try_from(col.checked_sub(self.min_col)?).ok()?;
+
+        if r < self.rows && c < self.cols {
+            Some(r * self.cols + c)
+        } else {
+            None
         }
+    }
 
-        let row = match self.canvas.rows.get(&self.min_row) {
-            None => String::new(),
-            Some(row) => match self
-                .max_col
-                .or_else(|| btree_minmax(row).map(|(_, &mc)| mc))
-            {
-                None => String::new(),
-                Some(max_c) => (self.min_col..=max_c)
-                    .map(|x| {
-                        row.get(&x)
-                            .map_or(BRAILLE_PATTERN_BLANK.to_string(), |pix| self.braille(pix))
-                    })
-                    .collect(),
-            },
-        };
+    // position of the pixel at the given index as (row, column)
+    fn position(&self, i: usize) -> (i32, i32) {
+        (
+            self.min_row + (i / self.cols) as i32,
+            self.min_col + (i % self.cols) as i32,
+        )
+    }
 
-        self.min_row += 1;
+    // the dots inside as (min x, min y, max x, max y), all inclusive
+    fn dots(&self) -> (i32, i32, i32, i32) {
+        (
+            self.min_col * 2,
+            self.min_row * 4,
+            (self.min_col + self.cols as i32) * 2 - 1,
+            (self.min_row + self.rows as i32) * 4 - 1,
+        )
+    }
+}
 
-        Some(row)
+impl HiddenLines {
+    /// The way to draw the hidden lines to switch to when cycling through all
+    /// of them.
+    pub fn next(self) -> Self {
+        match self {
+            HiddenLines::Show => HiddenLines::Hide,
+            HiddenLines::Hide => HiddenLines::Dim,
+            HiddenLines::Dim => HiddenLines::Dash,
+            HiddenLines::Dash => HiddenLines::Show,
+        }
+    }
+}
+
+impl std::str::FromStr for HiddenLines {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "show" => Ok(HiddenLines::Show),
+            "hide" => Ok(HiddenLines::Hide),
+            "dim" => Ok(HiddenLines::Dim),
+            "dash" => Ok(HiddenLines::Dash),
+            _ => Err("expected `show`, `hide`, `dim` or `dash`".to_string()),
+        }
     }
 }
 
@@ -304,7 +1185,7 @@ impl Default for Canvas {
 mod tests {
     use maplit::btreemap;
 
-    use super::{Canvas, Pixel, Vector3};
+    use super::{Canvas, Charset, HiddenLines, Pixel, Vector3};
 
     #[test]
     fn test_set() {
@@ -315,11 +1196,167 @@ mod tests {
         assert_eq!(
             c.rows,
             btreemap! {
-                0 => btreemap!{0 => Pixel {braille_offset: 1, z: 0.0}}
+                0 => btreemap!{0 => Pixel {
+                    braille_offset: 1,
+                    depth: [
+                        [0.0, f32::INFINITY],
+                        [f32::INFINITY, f32::INFINITY],
+                        [f32::INFINITY, f32::INFINITY],
+                        [f32::INFINITY, f32::INFINITY],
+                    ],
+                    shade: [[None; 2]; 4],
+                }}
             }
         );
     }
 
+    #[test]
+    fn test_depth() {
+        let mut c = Canvas::new();
+
+        c.set(Vector3::new(0.0, 0.0, 2.0));
+        c.set(Vector3::new(0.0, 0.0, 1.0));
+        c.set(Vector3::new(0.0, 0.0, 3.0));
+        assert_eq!(c.rows[&0][&0].depth[0][0], 1.0);
+
+        c.set(Vector3::new(1.0, 3.0, 5.0));
+        assert_eq!(c.rows[&0][&0].braille_offset, 0x81);
+        assert_eq!(c.rows[&0][&0].z(0xFF), 3.0);
+        assert_eq!(c.zrange(), Some((3.0, 3.0)));
+
+        // far triangles must not be visible behind near ones
+        c.clear();
+        c.fill_triangle(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(8.0, 0.0, 0.0),
+            Vector3::new(0.0, 8.0, 0.0),
+        );
+        c.fill_triangle(
+            Vector3::new(0.0, 0.0, 10.0),
+            Vector3::new(8.0, 0.0, 10.0),
+            Vector3::new(8.0, 8.0, 10.0),
+        );
+        assert_eq!(c.rows[&0][&0].z(0xFF), 0.0);
+        assert!(c.rows[&1][&3].z(0xFF) > 0.0);
+    }
+
+    #[test]
+    fn test_fill_triangle() {
+        let mut c = Canvas::new();
+
+        c.fill_triangle(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(7.0, 0.0, 7.0),
+            Vector3::new(0.0, 7.0, 7.0),
+        );
+
+        assert_eq!(c.rows(false).collect::<Vec<_>>(), vec!["⣿⣿⠟⠁", "⠟⠁"]);
+
+        // z is interpolated between the vertices
+        assert_eq!(c.rows[&0][&0].depth[0][0], 0.0);
+        assert_eq!(c.rows[&0][&3].depth[0][0], 6.0);
+        assert_eq!(c.rows[&1][&0].depth[2][0], 6.0);
+        assert_eq!(c.rows[&0][&1].depth[2][1], 5.0);
+    }
+
+    #[test]
+    fn test_fill_triangle_shaded() {
+        let mut c = Canvas::new();
+
+        c.fill_triangle_shaded(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(7.0, 0.0, 0.0),
+            Vector3::new(0.0, 7.0, 0.0),
+            [0.0, 0.7, 0.7],
+        );
+
+        assert_eq!(c.rows[&0][&0].shade[0][0], Some(0.0));
+        assert_eq!(c.rows[&0][&1].shade[2][1], Some(0.5));
+        assert_eq!(c.rows[&0][&0].shade(0xFF), Some(0.2));
+
+        // unshaded dots in front hide the shaded ones
+        c.set(Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(c.rows[&0][&0].shade[0][0], None);
+
+        assert_eq!(
+            c.rows(true).next(),
+            Some(format!(
+                "{}⣿{}⣿{}⠟{}⠁",
+                termion::color::Fg(termion::color::AnsiValue::grayscale(8)),
+                termion::color::Fg(termion::color::AnsiValue::grayscale(12)),
+                termion::color::Fg(termion::color::AnsiValue::grayscale(14)),
+                termion::color::Fg(termion::color::AnsiValue::grayscale(15)),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_fill_triangle_shared_edge() {
+        let mut c = Canvas::new();
+
+        // the two triangles share the diagonal of the square, its dots must be
+        // set only once.
+        let (a, b, d) = (
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(7.0, 0.0, 0.0),
+            Vector3::new(0.0, 7.0, 0.0),
+        );
+        let e = Vector3::new(7.0, 7.0, 0.0);
+
+        c.fill_triangle(a, b, d);
+        c.fill_triangle(b, e, d);
+
+        let mut n = 0;
+        for (_, row) in c.rows.iter() {
+            for (_, pix) in row.iter() {
+                n += pix.braille_offset.count_ones();
+            }
+        }
+
+        // the right and bottom edges are not part of the square
+        assert_eq!(n, 7 * 7);
+    }
+
+    #[test]
+    fn test_fill_small_triangle() {
+        let mut c = Canvas::new();
+
+        // no dot is inside, its outline is drawn instead
+        c.fill_triangle(
+            Vector3::new(0.2, 0.1, 0.0),
+            Vector3::new(0.4, 0.1, 0.0),
+            Vector3::new(0.3, 0.3, 0.0),
+        );
+        assert_eq!(c.rows(false).collect::<Vec<_>>(), vec!["⠁"]);
+
+        // same for slivers between two rows of dots
+        c.clear();
+        c.fill_triangle(
+            Vector3::new(0.0, 0.2, 0.0),
+            Vector3::new(3.0, 0.4, 0.0),
+            Vector3::new(0.0, 0.6, 0.0),
+        );
+        assert_eq!(c.rows(false).collect::<Vec<_>>(), vec!["⠛⠉"]);
+
+        // but slivers that contain a dot aren't outlined
+        c.clear();
+        c.fill_triangle(
+            Vector3::new(0.0, -0.2, 0.0),
+            Vector3::new(3.0, 0.4, 0.0),
+            Vector3::new(0.0, 0.2, 0.0),
+        );
+        assert_eq!(c.rows(false).collect::<Vec<_>>(), vec!["⠁"]);
+
+        // nor are the ones that only hide what's behind them
+        c.clear();
+        c.occlude(
+            Vector3::new(0.2, 0.1, 0.0),
+            Vector3::new(0.4, 0.1, 0.0),
+            Vector3::new(0.3, 0.3, 0.0),
+        );
+        assert!(c.rows.is_empty());
+    }
+
     #[test]
     fn test_clear() {
         let mut c = Canvas::new();
@@ -352,6 +1389,226 @@ mod tests {
         assert_eq!(c.rows(false).collect::<Vec<_>>(), vec!["⠁".to_string()]);
     }
 
+    #[test]
+    fn test_viewport() {
+        let draw = |c: &mut Canvas| {
+            c.fill_triangle(
+                Vector3::new(-5.0, -5.0, 0.0),
+                Vector3::new(15.0, -5.0, 7.0),
+                Vector3::new(-5.0, 15.0, 7.0),
+            );
+            c.line(
+                Vector3::new(-20.0, 3.0, -1.0),
+                Vector3::new(20.0, 3.0, -1.0),
+            );
+        };
+
+        let mut sparse = Canvas::new();
+        draw(&mut sparse);
+
+        let mut dense = Canvas::with_viewport(0, 1, 0, 2);
+        draw(&mut dense);
+
+        // everything outside of the viewport is clipped
+        assert_eq!(dense.dimensions(), Some((0, 1, 0, 2)));
+        assert_eq!(
+            dense.rows(false).collect::<Vec<_>>(),
+            sparse.frame(false, 0, 1, 0, Some(2)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            dense.frame(false, -1, 2, -1, Some(3)).collect::<Vec<_>>(),
+            vec!["", "⠀⣿⣿⣿⠀", "⠀⣿⡿⠋⠀", ""]
+        );
+        assert!(dense.is_set(0.0, 3.0));
+        assert!(!dense.is_set(-1.0, 3.0));
+        assert!(!dense.is_set(6.0, 3.0));
+
+        dense.clear();
+        assert_eq!(dense.dimensions(), None);
+        assert_eq!(dense.rows(false).count(), 0);
+    }
+
+    #[test]
+    fn test_viewport_clipping() {
+        let mut c = Canvas::with_viewport(0, 0, 0, 1);
+
+        // lines and triangles way bigger than the viewport only cost as much
+        // as their visible part.
+        c.line(
+            Vector3::new(-100_000.0, 1.0, 0.0),
+            Vector3::new(100_000.0, 1.0, 0.0),
+        );
+        c.fill_triangle(
+            Vector3::new(-1e9, 2.0, 0.0),
+            Vector3::new(1e9, 2.0, 0.0),
+            Vector3::new(0.0, 1e9, 0.0),
+        );
+        c.hidden_line(
+            Vector3::new(0.0, -1e9, 0.0),
+            Vector3::new(0.0, 1e9, 0.0),
+            HiddenLines::Show,
+        );
+
+        assert_eq!(c.rows(false).collect::<Vec<_>>(), vec!["⣷⣶"]);
+    }
+
+    #[test]
+    fn test_max_dot() {
+        let mut c = Canvas::new();
+        c.set_max_dot(3);
+
+        c.fill_triangle(
+            Vector3::new(-1e9, -1e9, 0.0),
+            Vector3::new(1e9, -1e9, 0.0),
+            Vector3::new(0.0, 1e9, 0.0),
+        );
+
+        assert_eq!(c.dimensions(), Some((-1, 0, -2, 1)));
+    }
+
+    #[test]
+    fn test_to_frame() {
+        let mut c = Canvas::new();
+        c.set(Vector3::new(0.0, 0.0, 2.0));
+        c.set(Vector3::new(1.0, 0.0, 4.0));
+        c.set(Vector3::new(4.0, 4.0, 1.0));
+
+        let frame = c.to_frame(true);
+        assert_eq!(frame.to_ansi(), c.rows(true).collect::<Vec<_>>());
+        assert_eq!(frame.to_text(), vec!["⠉", "⠀⠀⠁"]);
+
+        let depths = frame
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.depth).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(depths, vec![vec![Some(3.0)], vec![None, None, Some(1.0)]]);
+    }
+
+    #[test]
+    fn test_only_occluders() {
+        // nothing visible means there's no range of z to compute the
+        // brightness with
+        for &(charset, dither) in &[
+            (Charset::Braille, true),
+            (Charset::HalfBlock, false),
+            (Charset::HalfBlock, true),
+        ] {
+            for mut c in [Canvas::new(), Canvas::with_viewport(0, 1, 0, 1)] {
+                c.set_charset(charset);
+                c.set_dither(dither);
+                c.occlude(
+                    Vector3::new(0.0, 0.0, 1.0),
+                    Vector3::new(8.0, 0.0, 1.0),
+                    Vector3::new(0.0, 8.0, 1.0),
+                );
+
+                assert!(c
+                    .rows(false)
+                    .all(|row| row.chars().all(|ch| ch == ' ' || ch == '⠀')));
+                assert!(c.rows(true).count() > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill_triangles() {
+        let triangles = [
+            (
+                [
+                    Vector3::new(-3.0, -10.0, 5.0),
+                    Vector3::new(30.0, 20.0, 0.0),
+                    Vector3::new(2.0, 50.0, 5.0),
+                ],
+                None,
+            ),
+            (
+                [
+                    Vector3::new(0.0, 0.0, 1.0),
+                    Vector3::new(25.0, 40.0, 1.0),
+                    Vector3::new(25.0, 3.0, 1.0),
+                ],
+                Some([0.0, 0.5, 1.0]),
+            ),
+            // degenerate, drawn as a line going through all the rows. It's long
+            // enough that its dots depend on where it's stepped from.
+            (
+                [
+                    Vector3::new(-3001.0, -4093.0, -1.0),
+                    Vector3::new(7.0, 11.0, -1.0),
+                    Vector3::new(3015.0, 4115.0, -1.0),
+                ],
+                Some([1.0; 3]),
+            ),
+            // a sliver that only contains dots in the bottom rows, it must not
+            // be outlined on the others
+            (
+                [
+                    Vector3::new(10.2, -2.0, 2.0),
+                    Vector3::new(10.4, 45.0, 2.0),
+                    Vector3::new(12.9, 45.0, 2.0),
+                ],
+                None,
+            ),
+        ];
+
+        let mut expected = Canvas::with_viewport(0, 10, 0, 12);
+        for &([p0, p1, p2], shades) in &triangles {
+            match shades {
+                None => expected.fill_triangle(p0, p1, p2),
+                Some(shades) => expected.fill_triangle_shaded(p0, p1, p2, shades),
+            }
+        }
+
+        let mut c = Canvas::with_viewport(0, 10, 0, 12);
+        c.fill_triangles(&triangles);
+
+        assert_eq!(
+            c.rows(true).collect::<Vec<_>>(),
+            expected.rows(true).collect::<Vec<_>>()
+        );
+
+        // split in bands no matter how many threads there are
+        #[cfg(feature = "rayon")]
+        {
+            let mut c = Canvas::with_viewport(0, 10, 0, 12);
+            c.par_fill_triangles(&triangles);
+
+            assert_eq!(
+                c.rows(true).collect::<Vec<_>>(),
+                expected.rows(true).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_finite() {
+        let mut c = Canvas::new();
+        let origin = Vector3::new(0.0, 0.0, 0.0);
+        let nan = Vector3::new(f32::NAN, 0.0, 0.0);
+        let inf = Vector3::new(0.0, f32::INFINITY, 0.0);
+
+        c.set(nan);
+        c.set(inf);
+        c.set(Vector3::new(0.0, 0.0, f32::NAN));
+        c.set(Vector3::new(1e30, -1e30, 0.0));
+        c.line(origin, nan);
+        c.line(inf, origin);
+        c.fill_triangle(origin, nan, Vector3::new(4.0, 4.0, 0.0));
+        c.triangle(origin, Vector3::new(4.0, 4.0, 0.0), inf);
+        assert_eq!(c.dimensions(), None);
+
+        // huge triangles and lines are drawn only up to the limit of the
+        // canvas
+        c.fill_triangle(
+            Vector3::new(-1e30, 0.0, 0.0),
+            Vector3::new(1e30, 0.0, 0.0),
+            Vector3::new(0.0, 4.0, 0.0),
+        );
+        c.line(Vector3::new(-1e30, 8.0, 0.0), Vector3::new(1e30, 8.0, 0.0));
+        assert_eq!(c.dimensions(), Some((0, 2, -2048, 2048)));
+    }
+
     #[test]
     fn test_rect() {
         let mut c = Canvas::new();
@@ -376,6 +1633,138 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_charsets() {
+        let mut c = Canvas::new();
+
+        for x in 0..2 {
+            for y in 0..4 {
+                c.set(Vector3::new(x as f32, y as f32, 0.0));
+            }
+        }
+        for x in 2..4 {
+            for y in 0..2 {
+                c.set(Vector3::new(x as f32, y as f32, 0.0));
+            }
+        }
+        c.set(Vector3::new(4.0, 3.0, 0.0));
+
+        let mut render = |charset| {
+            c.set_charset(charset);
+            c.rows(false).collect::<Vec<_>>()
+        };
+
+        assert_eq!(render(Charset::Braille), vec!["⣿⠛⡀"]);
+        assert_eq!(render(Charset::HalfBlock), vec!["█▀▄"]);
+        assert_eq!(render(Charset::Quadrant), vec!["█▀▖"]);
+        assert_eq!(render(Charset::Sextant), vec!["█\u{1FB0E}\u{1FB0F}"]);
+        assert_eq!(render(Charset::Ascii), vec!["@+:"]);
+    }
+
+    #[test]
+    fn test_half_block_colors() {
+        let mut c = Canvas::new();
+        c.set_charset(Charset::HalfBlock);
+
+        c.set(Vector3::new(0.0, 0.0, 0.0));
+        c.set(Vector3::new(0.0, 3.0, 1.0));
+
+        // the nearest and farthest pixels
+        c.set(Vector3::new(2.0, 0.0, 0.0));
+        c.set(Vector3::new(4.0, 0.0, 1.0));
+
+        let row = c.rows(true).next().unwrap();
+        assert!(row.starts_with(&format!(
+            "{}{}▀{}",
+            termion::color::Fg(termion::color::AnsiValue::grayscale(23)),
+            termion::color::Bg(termion::color::AnsiValue::grayscale(4)),
+            termion::color::Bg(termion::color::Reset),
+        )));
+    }
+
+    #[test]
+    fn test_dither() {
+        let mut c = Canvas::new();
+        c.set_dither(true);
+
+        // every time closer to the camera to overwrite the previous dots
+        let mut render = |shade, z| {
+            for x in 0..4 {
+                for y in 0..4 {
+                    c.set_shaded(Vector3::new(x as f32, y as f32, z), Some(shade));
+                }
+            }
+
+            c.rows(false).collect::<Vec<_>>()
+        };
+
+        assert_eq!(render(0.0, 0.0), vec!["⠀⠀"]);
+        assert_eq!(render(0.5, -1.0), vec!["⢕⢕"]);
+        assert_eq!(render(1.0, -2.0), vec!["⣿⣿"]);
+    }
+
+    #[test]
+    fn test_hidden_line() {
+        let render = |hidden| {
+            let mut c = Canvas::new();
+
+            // a square in front of the middle of the line
+            c.occlude(
+                Vector3::new(4.0, 0.0, 0.0),
+                Vector3::new(11.0, 0.0, 0.0),
+                Vector3::new(4.0, 7.0, 0.0),
+            );
+            c.occlude(
+                Vector3::new(11.0, 0.0, 0.0),
+                Vector3::new(11.0, 7.0, 0.0),
+                Vector3::new(4.0, 7.0, 0.0),
+            );
+            assert_eq!(c.rows(false).next(), Some("⠀⠀⠀⠀".to_string()));
+
+            c.hidden_line(
+                Vector3::new(0.0, 1.0, 1.0),
+                Vector3::new(15.0, 1.0, 1.0),
+                hidden,
+            );
+            c
+        };
+        let row = |c: &Canvas| c.rows(false).next().unwrap();
+
+        assert_eq!(row(&render(HiddenLines::Show)), "⠒⠒⠒⠒⠒⠒⠒⠒");
+        assert_eq!(row(&render(HiddenLines::Hide)), "⠒⠒⠀⠀⠀⠐⠒⠒");
+        assert_eq!(row(&render(HiddenLines::Dash)), "⠒⠒⠒⠀⠒⠐⠒⠒");
+
+        let c = render(HiddenLines::Dim);
+        assert_eq!(row(&c), "⠒⠒⠒⠒⠒⠒⠒⠒");
+        assert_eq!(c.rows[&0][&2].shade[1][0], Some(0.2));
+        assert_eq!(c.rows[&0][&1].shade[1][0], None);
+    }
+
+    #[test]
+    fn test_image() {
+        let mut c = Canvas::new();
+
+        c.set(Vector3::new(0.0, 0.0, 0.0));
+        c.set(Vector3::new(3.0, 5.0, 1.0));
+        c.set(Vector3::new(-1.0, 0.0, 0.0));
+        c.set(Vector3::new(10.0, 10.0, 0.0));
+
+        let img = c.image(true, 0, 0, 4, 6);
+        assert_eq!((img.width, img.height), (4, 6));
+        assert_eq!(img.get(0, 0), Some((238, 238, 238)));
+        assert_eq!(img.get(3, 5), Some((48, 48, 48)));
+
+        let set = (0..6)
+            .flat_map(|y| (0..4).map(move |x| (x, y)))
+            .filter(|&(x, y)| img.get(x, y).is_some())
+            .count();
+        assert_eq!(set, 2);
+
+        let img = c.image(false, -1, 0, 2, 1);
+        assert_eq!(img.get(0, 0), Some((238, 238, 238)));
+        assert_eq!(img.get(1, 0), Some((238, 238, 238)));
+    }
+
     #[test]
     fn test_sine_example() {
         let mut s = Canvas::new();
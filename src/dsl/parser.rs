@@ -17,7 +17,7 @@ pub enum ParseErrorKind<'input> {
     BadIdentifier(&'input str),
 }
 
-pub fn parse_module(input: &str) -> Result<Module> {
+pub fn parse_module(input: &str) -> Result<'_, Module<'_>> {
     let stmts = input
         .lines()
         .enumerate()
@@ -134,7 +134,13 @@ where
 
     fn parse_f32(&mut self) -> Result<'input, f32> {
         let num = self.next("number")?;
-        f32::from_str(num).or_else(|_| self.error(ParseErrorKind::BadNumber(num)))
+
+        // Rust happily parses `inf` and `NaN` as well as numbers too big to
+        // fit, none of them make sense as a coordinate.
+        match f32::from_str(num) {
+            Ok(n) if n.is_finite() => Ok(n),
+            _ => self.error(ParseErrorKind::BadNumber(num)),
+        }
     }
 
     fn eat(&mut self, what: &'static str) -> Result<'input, ()> {
@@ -267,6 +273,18 @@ mod tests {
                 kind: BadNumber("0.98a"),
             })
         );
+
+        for num in &["inf", "-inf", "NaN", "1e39"] {
+            let line = format!("vertex v = 0 {} 0", num);
+            assert_eq!(
+                parse_module(&line),
+                Err(ast::Error {
+                    line_no: 0,
+                    line: &line,
+                    kind: BadNumber(num),
+                })
+            );
+        }
     }
 
     #[test]
@@ -0,0 +1,330 @@
+//! Frames rendered by a Canvas as a grid of cells, that can be inspected,
+//! restyled and then serialized to ANSI escape codes, plain text, HTML or
+//! JSON.
+
+use std::fmt::Write;
+
+use termion::color::{Bg, Fg, Reset};
+
+use crate::palette::Color;
+
+/// A frame as rows of cells, see `Canvas::to_frame` and `Rows::into_frame`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The cells row by row from the top, rows can have different lengths.
+    pub rows: Vec<Vec<Cell>>,
+
+    /// The color of the background the frame is drawn on, `None` for the
+    /// default background of the terminal.
+    pub background: Option<Color>,
+}
+
+/// A character of a frame along with its colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub glyph: char,
+
+    /// The average depth of what's drawn in the cell, the smaller the closer
+    /// to the camera it is. `None` if there's nothing drawn.
+    pub depth: Option<f32>,
+
+    /// The color of the glyph, `None` for the default one of the terminal.
+    pub fg: Option<Color>,
+
+    /// The color behind the glyph, `None` for the background of the frame.
+    pub bg: Option<Color>,
+}
+
+impl Cell {
+    /// A cell with nothing drawn in it and the default colors.
+    pub fn new(glyph: char) -> Self {
+        Cell {
+            glyph,
+            depth: None,
+            fg: None,
+            bg: None,
+        }
+    }
+
+    // whether the foreground color doesn't matter because there's nothing to
+    // color
+    pub(crate) fn is_blank(&self) -> bool {
+        self.glyph.is_whitespace() || self.glyph == '\u{2800}'
+    }
+
+    // whether the cells look the same on screen, no matter their depth
+    pub(crate) fn looks_like(&self, other: &Cell) -> bool {
+        self.glyph == other.glyph && self.fg == other.fg && self.bg == other.bg
+    }
+}
+
+impl Frame {
+    /// The number of cells of the longest row.
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// The rows with the escape codes to color each cell, like the ones
+    /// returned by `Canvas::rows`.
+    pub fn to_ansi(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .map(|row| ansi_row(row, self.background))
+            .collect()
+    }
+
+    /// The rows with the glyphs only.
+    pub fn to_text(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.glyph).collect())
+            .collect()
+    }
+
+    /// A `pre` element with a `span` for each run of cells of the same
+    /// colors.
+    pub fn to_html(&self) -> String {
+        let mut html = match self.background {
+            None => "<pre>".to_string(),
+            Some(bg) => format!("<pre style=\"background-color:{}\">", hex(bg)),
+        };
+
+        for (r, row) in self.rows.iter().enumerate() {
+            if r > 0 {
+                html.push('\n');
+            }
+
+            let mut run: Option<(Option<Color>, Option<Color>)> = None;
+            for cell in row {
+                // blank cells don't need a color of their own, they can join
+                // the current run instead.
+                let colors = if cell.is_blank() && cell.bg.is_none() {
+                    run.unwrap_or((None, None))
+                } else {
+                    (cell.fg, cell.bg)
+                };
+
+                if run != Some(colors) {
+                    if run.is_some_and(|run| run != (None, None)) {
+                        html += "</span>";
+                    }
+
+                    match colors {
+                        (None, None) => {}
+                        (fg, bg) => {
+                            let mut style = vec![];
+                            if let Some(fg) = fg {
+                                style.push(format!("color:{}", hex(fg)));
+                            }
+                            if let Some(bg) = bg {
+                                style.push(format!("background-color:{}", hex(bg)));
+                            }
+                            write!(html, "<span style=\"{}\">", style.join(";")).unwrap();
+                        }
+                    }
+                    run = Some(colors);
+                }
+
+                match cell.glyph {
+                    '<' => html += "&lt;",
+                    '>' => html += "&gt;",
+                    '&' => html += "&amp;",
+                    c => html.push(c),
+                }
+            }
+
+            if run.is_some_and(|run| run != (None, None)) {
+                html += "</span>";
+            }
+        }
+
+        html += "</pre>";
+        html
+    }
+
+    /// An object with the `background` color and the `rows` of cells, each
+    /// with its `glyph`, `depth`, `fg` and `bg` colors. Colors are hex
+    /// strings like `"#ff8000"` or `null` for the default ones.
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"background\":{},\"rows\":[",
+            json_color(self.background)
+        );
+
+        for (r, row) in self.rows.iter().enumerate() {
+            if r > 0 {
+                json.push(',');
+            }
+
+            json.push('[');
+            for (c, cell) in row.iter().enumerate() {
+                if c > 0 {
+                    json.push(',');
+                }
+
+                let depth = cell
+                    .depth
+                    .filter(|z| z.is_finite())
+                    .map_or("null".to_string(), |z| z.to_string());
+                write!(
+                    json,
+                    "{{\"glyph\":{},\"depth\":{},\"fg\":{},\"bg\":{}}}",
+                    json_string(cell.glyph),
+                    depth,
+                    json_color(cell.fg),
+                    json_color(cell.bg)
+                )
+                .unwrap();
+            }
+            json.push(']');
+        }
+
+        json += "]}";
+        json
+    }
+}
+
+// Format the given cells with the escape codes to color them. The cells with a
+// background color restore `background` afterwards.
+pub(crate) fn ansi_row(cells: &[Cell], background: Option<Color>) -> String {
+    let mut row = String::new();
+
+    for cell in cells {
+        if let Some(fg) = cell.fg {
+            write!(row, "{}", Fg(fg)).unwrap();
+        }
+
+        match cell.bg {
+            None => row.push(cell.glyph),
+            Some(bg) => {
+                write!(row, "{}{}", Bg(bg), cell.glyph).unwrap();
+                row += &background_escape(background);
+            }
+        }
+    }
+
+    row
+}
+
+// the escape code to switch to the given background, `None` for the default
+// one of the terminal.
+pub(crate) fn background_escape(background: Option<Color>) -> String {
+    match background {
+        None => Bg(Reset).to_string(),
+        Some(bg) => Bg(bg).to_string(),
+    }
+}
+
+fn hex(color: Color) -> String {
+    let (r, g, b) = color.rgb();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn json_color(color: Option<Color>) -> String {
+    color.map_or("null".to_string(), |c| format!("\"{}\"", hex(c)))
+}
+
+fn json_string(c: char) -> String {
+    match c {
+        '"' => "\"\\\"\"".to_string(),
+        '\\' => "\"\\\\\"".to_string(),
+        c if c.is_control() => format!("\"\\u{:04x}\"", u32::from(c)),
+        c => format!("\"{}\"", c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cell, Color, Frame};
+
+    use termion::color::{Bg, Fg, Reset};
+
+    fn frame() -> Frame {
+        let red = Color::Rgb(255, 0, 0);
+
+        Frame {
+            rows: vec![
+                vec![
+                    Cell {
+                        depth: Some(1.5),
+                        fg: Some(red),
+                        ..Cell::new('⣿')
+                    },
+                    Cell::new('⠀'),
+                    Cell {
+                        depth: Some(-2.0),
+                        fg: Some(red),
+                        bg: Some(Color::Ansi256(16)),
+                        ..Cell::new('<')
+                    },
+                ],
+                vec![],
+                vec![Cell::new('"')],
+            ],
+            background: None,
+        }
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let frame = frame();
+        assert_eq!(frame.width(), 3);
+        assert_eq!(frame.height(), 3);
+    }
+
+    #[test]
+    fn test_ansi() {
+        let red = Color::Rgb(255, 0, 0);
+
+        assert_eq!(
+            frame().to_ansi(),
+            vec![
+                format!(
+                    "{}⣿⠀{}{}<{}",
+                    Fg(red),
+                    Fg(red),
+                    Bg(Color::Ansi256(16)),
+                    Bg(Reset)
+                ),
+                String::new(),
+                "\"".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(frame().to_text(), vec!["⣿⠀<", "", "\""]);
+    }
+
+    #[test]
+    fn test_html() {
+        assert_eq!(
+            frame().to_html(),
+            "<pre><span style=\"color:#ff0000\">⣿⠀</span>\
+             <span style=\"color:#ff0000;background-color:#000000\">&lt;</span>\n\n\"</pre>"
+        );
+
+        let mut frame = frame();
+        frame.background = Some(Color::Rgb(0, 0, 255));
+        assert!(frame
+            .to_html()
+            .starts_with("<pre style=\"background-color:#0000ff\">"));
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            frame().to_json(),
+            "{\"background\":null,\"rows\":[[\
+             {\"glyph\":\"⣿\",\"depth\":1.5,\"fg\":\"#ff0000\",\"bg\":null},\
+             {\"glyph\":\"⠀\",\"depth\":null,\"fg\":null,\"bg\":null},\
+             {\"glyph\":\"<\",\"depth\":-2,\"fg\":\"#ff0000\",\"bg\":\"#000000\"}\
+             ],[],[{\"glyph\":\"\\\"\",\"depth\":null,\"fg\":null,\"bg\":null}]]}"
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Images made of real pixels and the escape sequences to display them in the
+//! terminals that support either the Sixel or the Kitty graphics protocol.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::palette::{Color, ColorDepth};
+
+/// An RGB image whose pixels can also be transparent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Option<(u8, u8, u8)>>,
+}
+
+/// A protocol to display images in a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Sixel,
+    Kitty,
+}
+
+// the Kitty protocol wants the payload to be split in chunks of at most this
+// size.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+// Sixel terminals are only required to support this many color registers.
+const SIXEL_MAX_COLORS: usize = 256;
+
+static BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl Image {
+    /// Create a new fully transparent Image.
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![None; width * height],
+        }
+    }
+
+    /// The color of the pixel at the given coordinates, `None` if it's
+    /// transparent.
+    ///
+    /// Panics if the coordinates are out of bounds.
+    pub fn get(&self, x: usize, y: usize) -> Option<(u8, u8, u8)> {
+        assert!(x < self.width && y < self.height);
+        self.pixels[y * self.width + x]
+    }
+
+    /// Set the color of the pixel at the given coordinates.
+    ///
+    /// Panics if the coordinates are out of bounds.
+    pub fn set(&mut self, x: usize, y: usize, color: Option<(u8, u8, u8)>) {
+        assert!(x < self.width && y < self.height);
+        self.pixels[y * self.width + x] = color;
+    }
+}
+
+impl Protocol {
+    /// Detect the graphics protocol supported by the terminal from the
+    /// environment, `None` if it doesn't seem to support any.
+    pub fn detect() -> Option<Protocol> {
+        let var = |name| std::env::var(name).ok();
+
+        Protocol::from_env(
+            var("TERM").as_deref(),
+            var("TERM_PROGRAM").as_deref(),
+            var("KITTY_WINDOW_ID").is_some(),
+        )
+    }
+
+    /// Like `detect`, but with the values of the `TERM` and `TERM_PROGRAM`
+    /// environment variables and whether `KITTY_WINDOW_ID` is set passed
+    /// explicitly.
+    pub fn from_env(
+        term: Option<&str>,
+        term_program: Option<&str>,
+        kitty_window_id: bool,
+    ) -> Option<Protocol> {
+        let term = term.unwrap_or("");
+
+        if kitty_window_id
+            || term == "xterm-kitty"
+            || matches!(term_program, Some("WezTerm") | Some("ghostty"))
+        {
+            return Some(Protocol::Kitty);
+        }
+
+        if term.starts_with("foot")
+            || ["mlterm", "contour", "sixel"]
+                .iter()
+                .any(|t| term.contains(t))
+        {
+            return Some(Protocol::Sixel);
+        }
+
+        None
+    }
+
+    /// The escape sequence that displays the image at the cursor position.
+    pub fn encode(self, image: &Image) -> String {
+        match self {
+            Protocol::Sixel => sixel(image),
+            Protocol::Kitty => kitty(image),
+        }
+    }
+
+    /// The escape sequence that removes the images displayed so far, if they
+    /// aren't removed by clearing the screen already.
+    pub fn clear(self) -> &'static str {
+        match self {
+            Protocol::Sixel => "",
+            Protocol::Kitty => "\x1b_Ga=d,q=2\x1b\\",
+        }
+    }
+}
+
+// Encode the image with the Sixel protocol. The image is drawn in bands 6
+// pixels high, one color at a time.
+fn sixel(image: &Image) -> String {
+    let mut colors = HashMap::new();
+    for &c in image.pixels.iter().flatten() {
+        let n = colors.len();
+        colors.entry(c).or_insert(n);
+    }
+
+    // terminals have a limited number of color registers, fallback to the 256
+    // colors palette if there are too many colors.
+    let quantize = colors.len() > SIXEL_MAX_COLORS;
+    let color_of = |(r, g, b)| {
+        if quantize {
+            Color::Rgb(r, g, b).quantize(ColorDepth::Ansi256).rgb()
+        } else {
+            (r, g, b)
+        }
+    };
+    if quantize {
+        colors.clear();
+        for &c in image.pixels.iter().flatten() {
+            let n = colors.len();
+            colors.entry(color_of(c)).or_insert(n);
+        }
+    }
+
+    // P2 = 1 leaves the pixels that are not painted transparent
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", image.width, image.height);
+
+    let mut registers = colors.iter().map(|(&c, &i)| (i, c)).collect::<Vec<_>>();
+    registers.sort_unstable();
+    for (i, (r, g, b)) in registers {
+        let percent = |c: u8| u32::from(c) * 100 / 255;
+        write!(out, "#{};2;{};{};{}", i, percent(r), percent(g), percent(b)).unwrap();
+    }
+
+    let indices = image
+        .pixels
+        .iter()
+        .map(|p| p.map(|c| colors[&color_of(c)]))
+        .collect::<Vec<_>>();
+    let index = |x, y| indices[y * image.width + x];
+
+    for y0 in (0..image.height).step_by(6) {
+        let band = y0..image.height.min(y0 + 6);
+
+        let mut band_colors = band
+            .clone()
+            .flat_map(|y| (0..image.width).filter_map(move |x| index(x, y)))
+            .collect::<Vec<_>>();
+        band_colors.sort_unstable();
+        band_colors.dedup();
+
+        for color in band_colors {
+            write!(out, "#{}", color).unwrap();
+
+            let sixels = (0..image.width).map(|x| {
+                band.clone().fold(0, |bits, y| {
+                    if index(x, y) == Some(color) {
+                        bits | 1 << (y - y0)
+                    } else {
+                        bits
+                    }
+                })
+            });
+
+            // run-length encode the repeated sixels
+            let mut run: Option<(u8, usize)> = None;
+            for s in sixels.map(Some).chain(std::iter::once(None)) {
+                match (run, s) {
+                    (Some((prev, n)), Some(s)) if prev == s => run = Some((prev, n + 1)),
+                    _ => {
+                        if let Some((prev, n)) = run {
+                            let c = char::from(63 + prev);
+                            if n > 3 {
+                                write!(out, "!{}{}", n, c).unwrap();
+                            } else {
+                                out.extend(std::iter::repeat_n(c, n));
+                            }
+                        }
+                        run = s.map(|s| (s, 1));
+                    }
+                }
+            }
+
+            // go back to the start of the band for the next color
+            out.push('$');
+        }
+
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+// Encode the image with the Kitty graphics protocol as RGBA pixels, the
+// terminal is asked not to reply to not mess with the input.
+fn kitty(image: &Image) -> String {
+    let rgba = image
+        .pixels
+        .iter()
+        .flat_map(|p| match *p {
+            None => [0, 0, 0, 0],
+            Some((r, g, b)) => [r, g, b, 255],
+        })
+        .collect::<Vec<_>>();
+
+    let payload = base64(&rgba);
+    let chunks = payload
+        .as_bytes()
+        .chunks(KITTY_CHUNK_SIZE)
+        .collect::<Vec<_>>();
+
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap();
+
+        if i == 0 {
+            write!(
+                out,
+                "\x1b_Ga=T,f=32,s={},v={},q=2,m={};{}\x1b\\",
+                image.width, image.height, more, chunk
+            )
+            .unwrap();
+        } else {
+            write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk).unwrap();
+        }
+    }
+
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(
+                    BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize],
+                ));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, Image, Protocol};
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Protocol::from_env(None, None, false), None);
+        assert_eq!(
+            Protocol::from_env(Some("xterm-256color"), None, false),
+            None
+        );
+
+        assert_eq!(
+            Protocol::from_env(Some("xterm-kitty"), None, false),
+            Some(Protocol::Kitty)
+        );
+        assert_eq!(
+            Protocol::from_env(Some("xterm-256color"), None, true),
+            Some(Protocol::Kitty)
+        );
+        assert_eq!(
+            Protocol::from_env(Some("xterm-256color"), Some("WezTerm"), false),
+            Some(Protocol::Kitty)
+        );
+
+        assert_eq!(
+            Protocol::from_env(Some("foot"), None, false),
+            Some(Protocol::Sixel)
+        );
+        assert_eq!(
+            Protocol::from_env(Some("mlterm"), None, false),
+            Some(Protocol::Sixel)
+        );
+    }
+
+    #[test]
+    fn test_sixel() {
+        let mut img = Image::new(5, 7);
+        for x in 0..5 {
+            img.set(x, 0, Some((255, 0, 0)));
+        }
+        img.set(0, 1, Some((255, 0, 0)));
+        img.set(4, 6, Some((0, 0, 255)));
+
+        assert_eq!(
+            Protocol::Sixel.encode(&img),
+            "\x1bP0;1;0q\"1;1;5;7#0;2;100;0;0#1;2;0;0;100#0B!4@$-#1!4?@$-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_kitty() {
+        let mut img = Image::new(1, 1);
+        img.set(0, 0, Some((255, 0, 0)));
+
+        assert_eq!(
+            Protocol::Kitty.encode(&img),
+            "\x1b_Ga=T,f=32,s=1,v=1,q=2,m=0;/wAA/w==\x1b\\"
+        );
+
+        // big images are sent in chunks
+        let img = Image::new(64, 48);
+        let encoded = Protocol::Kitty.encode(&img);
+        assert_eq!(encoded.matches("\x1b_G").count(), 4);
+        assert!(encoded.starts_with("\x1b_Ga=T,f=32,s=64,v=48,q=2,m=1;AAAA"));
+        assert!(encoded.contains("\x1b\\\x1b_Gm=1;AAAA"));
+        assert!(encoded.contains("\x1b\\\x1b_Gm=0;AAAA"));
+        assert!(encoded.ends_with("AAAA\x1b\\"));
+    }
+}
@@ -1,6 +1,17 @@
+pub mod bounds;
+pub mod camera;
 pub mod drawille;
 pub mod dsl;
+pub mod frame;
+pub mod graphics;
+pub mod matrix4;
+pub mod mesh;
+pub mod palette;
+pub mod quaternion;
+pub mod screen;
 pub mod stl;
 pub mod vector3;
 
+pub use self::matrix4::Matrix4;
+pub use self::quaternion::Quaternion;
 pub use self::vector3::Vector3;
@@ -4,20 +4,38 @@ use std::{
     io::{self, Read, Write},
     path::{Path, PathBuf},
     process::exit,
+    sync::Arc,
     time,
 };
 
 use clap::Parser;
 
-use termion::{input::TermRead, raw::IntoRawMode};
+use termion::{
+    event::{Event, Key, MouseButton, MouseEvent},
+    input::{MouseTerminal, TermRead},
+    raw::IntoRawMode,
+    screen::IntoAlternateScreen,
+};
 
-use termesh::{drawille::Canvas, dsl, stl::Stl, Vector3};
+use termesh::{
+    bounds::{self, BoundingBox, BoundingSphere},
+    camera::{Camera, Projection},
+    drawille::{charset::Charset, Canvas, HiddenLines},
+    dsl,
+    frame::Cell,
+    graphics::Protocol,
+    mesh::{Edge, IndexedMesh},
+    palette::{ColorDepth, Colormap, Palette},
+    screen::Screen,
+    stl::Stl,
+    Matrix4, Quaternion, Vector3,
+};
 
 /// Display 3D objects in the terminal using Braille characters.
 #[derive(Debug, Parser)]
 struct App {
     /// Scale the input mesh by a given factor. If passed disables autoscaling.
-    #[arg(short = 's', long = "scale")]
+    #[arg(short = 's', long = "scale", value_parser = parse_finite)]
     scale: Option<f32>,
 
     /// Rotate the input mesh around the x axis by a given angle in radians
@@ -50,6 +68,12 @@ struct App {
     )]
     rotation_z: f32,
 
+    /// Point the mesh is rotated around. It can be either `center` for the
+    /// center of the bounding box, `centroid` for the average of the vertices
+    /// or an arbitrary point in the `x,y,z` format.
+    #[arg(long = "pivot", default_value = "center", allow_hyphen_values = true)]
+    pivot: Pivot,
+
     /// Do not render using true colors. This will effectively make the depth
     /// all the same.
     #[arg(long = "no-depth")]
@@ -59,6 +83,86 @@ struct App {
     #[arg(short = 'w', long = "wireframe")]
     only_wireframe: bool,
 
+    /// Display only the silhouette, the boundary and the sharp edges of the
+    /// mesh like in a technical illustration.
+    #[arg(short = 'e', long = "edges", conflicts_with = "only_wireframe")]
+    only_edges: bool,
+
+    /// How to draw the lines hidden by the mesh in wireframe and edges mode.
+    /// It can be either `show`, `hide`, `dim` to draw them darker or `dash`.
+    #[arg(long = "hidden-lines", default_value = "show")]
+    hidden_lines: HiddenLines,
+
+    /// Fill also the facets of STL meshes that face away from the camera.
+    /// They're usually hidden by the rest of a closed mesh and skipping them
+    /// makes rendering faster.
+    #[arg(long = "no-cull")]
+    no_cull: bool,
+
+    /// How to color the filled mesh. It can be either `depth` to make the
+    /// parts closer to the camera brighter, `flat` to light each facet by its
+    /// orientation or `smooth` to interpolate the light across the facets.
+    #[arg(long = "shading", default_value = "depth")]
+    shading: Shading,
+
+    /// Maximum angle in degrees between two adjacent facets for the edge
+    /// between them to be smoothed by `smooth` shading. Sharper edges are the
+    /// ones displayed by `--edges`.
+    #[arg(long = "crease-angle", default_value = "45")]
+    crease_angle: f32,
+
+    /// Colors used for depth and shading. It can be either `grayscale`,
+    /// `viridis`, `inferno`, `turbo` or a gradient between a list of colors
+    /// like `#000080,#ffffff`.
+    #[arg(long = "colormap", default_value = "grayscale")]
+    colormap: Colormap,
+
+    /// When to use colors. It can be either `auto` to use them only if the
+    /// terminal supports them, `always` or `never`.
+    #[arg(long = "color", default_value = "auto")]
+    color: ColorChoice,
+
+    /// Adapt the colors to a terminal with a light background, closer parts
+    /// of the mesh and lit facets are darker.
+    #[arg(long = "light-background")]
+    light_background: bool,
+
+    /// Characters used to draw the mesh. It can be either `braille` for the
+    /// highest resolution, `half-block`, `quadrant` and `sextant` for block
+    /// elements or `ascii` for terminals without Unicode support.
+    #[arg(long = "charset", default_value = "braille")]
+    charset: Charset,
+
+    /// Light the dots of the filled mesh by how bright they are, so that the
+    /// shading is visible as dot density even without colors.
+    #[arg(long = "dither")]
+    dither: bool,
+
+    /// Display the mesh as an image made of real pixels instead of
+    /// characters. It can be either `never`, `auto` to use the protocol the
+    /// terminal seems to support, `sixel` or `kitty`.
+    #[arg(long = "graphics", default_value = "never")]
+    graphics: GraphicsChoice,
+
+    /// Direction towards the light in the `x,y,z` format relative to the
+    /// screen, that is X points right, Y down and Z into the screen.
+    #[arg(
+        long = "light",
+        default_value = "-1,-1,-2",
+        allow_hyphen_values = true,
+        value_parser = parse_point
+    )]
+    light: Vector3,
+
+    /// Render using a perspective projection instead of an orthographic one.
+    #[arg(long = "perspective")]
+    perspective: bool,
+
+    /// Vertical field of view in degrees of the perspective projection, it
+    /// must be between 0 and 180 degrees.
+    #[arg(long = "fov", default_value = "60", value_parser = parse_fov)]
+    fov: f32,
+
     /// Display a mesh and exit.
     #[arg(long = "non-interactive")]
     non_interactive: bool,
@@ -69,59 +173,615 @@ struct App {
     mesh_filepath: PathBuf,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pivot {
+    Center,
+    Centroid,
+    Point(Vector3),
+}
+
+impl std::str::FromStr for Pivot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "center" => Ok(Pivot::Center),
+            "centroid" => Ok(Pivot::Centroid),
+            _ => parse_point(s)
+                .map(Pivot::Point)
+                .map_err(|err| format!("expected `center`, `centroid` or a point, {}", err)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl std::str::FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err("expected `auto`, `always` or `never`".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GraphicsChoice {
+    Never,
+    Auto,
+    Sixel,
+    Kitty,
+}
+
+impl std::str::FromStr for GraphicsChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(GraphicsChoice::Never),
+            "auto" => Ok(GraphicsChoice::Auto),
+            "sixel" => Ok(GraphicsChoice::Sixel),
+            "kitty" => Ok(GraphicsChoice::Kitty),
+            _ => Err("expected `never`, `auto`, `sixel` or `kitty`".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shading {
+    Depth,
+    Flat,
+    Smooth,
+}
+
+impl Shading {
+    fn next(self) -> Self {
+        match self {
+            Shading::Depth => Shading::Flat,
+            Shading::Flat => Shading::Smooth,
+            Shading::Smooth => Shading::Depth,
+        }
+    }
+}
+
+impl std::str::FromStr for Shading {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depth" => Ok(Shading::Depth),
+            "flat" => Ok(Shading::Flat),
+            "smooth" => Ok(Shading::Smooth),
+            _ => Err("expected `depth`, `flat` or `smooth`".to_string()),
+        }
+    }
+}
+
+fn parse_point(s: &str) -> Result<Vector3, String> {
+    let coords = s
+        .split(',')
+        .map(parse_finite)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("bad coordinate: {}", err))?;
+
+    match coords[..] {
+        [x, y, z] => Ok(Vector3::new(x, y, z)),
+        _ => Err("expected a point like `x,y,z`".to_string()),
+    }
+}
+
+fn parse_fov(s: &str) -> Result<f32, String> {
+    let fov = parse_finite(s).map_err(|err| format!("bad angle: {}", err))?;
+
+    if fov > 0.0 && fov < 180.0 {
+        Ok(fov)
+    } else {
+        Err("expected an angle in degrees greater than 0 and less than 180".to_string())
+    }
+}
+
+// parse a number rejecting NaN and the ones too big to be represented
+fn parse_finite(s: &str) -> Result<f32, String> {
+    let n = s.trim().parse::<f32>().map_err(|err| err.to_string())?;
+
+    if n.is_finite() {
+        Ok(n)
+    } else {
+        Err(format!("`{}` is not a finite number", s.trim()))
+    }
+}
+
+// State of the interactive viewer that's not part of the configuration.
+#[derive(Debug, Clone, PartialEq)]
+struct View {
+    // bounding sphere around the pivot, it doesn't change while rotating
+    // therefore it's used to frame the mesh.
+    bounds: BoundingSphere,
+
+    // rotation of the mesh around the pivot
+    orientation: Quaternion,
+    fine_steps: bool,
+
+    zoom: f32,
+
+    // offset of the mesh on the screen in canvas space
+    pan: Vector3,
+
+    // the mouse button being held and the last position of the mouse
+    drag: Option<(MouseButton, u16, u16)>,
+}
+
+impl View {
+    fn new<S: Scene>(scene: &S, config: &App) -> Self {
+        View {
+            bounds: BoundingSphere::around(pivot_point(scene, config.pivot), scene.vertices()),
+            orientation: Quaternion::from_euler(
+                config.rotation_x,
+                config.rotation_y,
+                config.rotation_z,
+            ),
+            fine_steps: false,
+            zoom: 1.0,
+            pan: Vector3::new(0.0, 0.0, 0.0),
+            drag: None,
+        }
+    }
+
+    fn recenter<S: Scene>(&mut self, scene: &S, pivot: Pivot) {
+        self.bounds = BoundingSphere::around(pivot_point(scene, pivot), scene.vertices());
+        self.pan = Vector3::new(0.0, 0.0, 0.0);
+    }
+
+    // go back to the initial framing, orientation is left untouched
+    fn fit(&mut self) {
+        self.zoom = 1.0;
+        self.pan = Vector3::new(0.0, 0.0, 0.0);
+    }
+
+    fn rotation_step(&self) -> f32 {
+        if self.fine_steps {
+            PI / 36.0
+        } else {
+            PI / 6.0
+        }
+    }
+
+    // rotate around an axis in screen space by the given number of steps,
+    // that is rotations are always relative to the current view.
+    fn rotate(&mut self, axis: Vector3, steps: f32) {
+        let q = Quaternion::from_axis_angle(axis, steps * self.rotation_step());
+        self.orientation = (q * self.orientation).normalized();
+    }
+
+    // Update the view according to the mouse event, returns whether the view
+    // changed. Dragging with the left button orbits around the pivot,
+    // dragging with any other button pans and the wheel zooms.
+    fn handle_mouse(&mut self, ev: MouseEvent) -> bool {
+        // how many radians a drag of one cell rotates the mesh by
+        let angle_per_cell = PI / 90.0;
+        let zoom_inc = 1.1;
+
+        match ev {
+            MouseEvent::Press(MouseButton::WheelUp, _, _) => {
+                self.zoom *= zoom_inc;
+                true
+            }
+            MouseEvent::Press(MouseButton::WheelDown, _, _) => {
+                self.zoom /= zoom_inc;
+                true
+            }
+            MouseEvent::Press(button, x, y) => {
+                self.drag = Some((button, x, y));
+                false
+            }
+            MouseEvent::Release(_, _) => {
+                self.drag = None;
+                false
+            }
+            MouseEvent::Hold(x, y) => {
+                let (button, last_x, last_y) = match self.drag {
+                    None => return false,
+                    Some(drag) => drag,
+                };
+                self.drag = Some((button, x, y));
+
+                let dx = f32::from(x) - f32::from(last_x);
+                let dy = f32::from(y) - f32::from(last_y);
+                if dx == 0.0 && dy == 0.0 {
+                    return false;
+                }
+
+                if button == MouseButton::Left {
+                    // rotate around the axis perpendicular to the drag
+                    // direction so that the side facing the viewer follows
+                    // the mouse.
+                    let axis = Vector3::new(dy, -dx, 0.0);
+                    let q = Quaternion::from_axis_angle(axis, axis.norm() * angle_per_cell);
+                    self.orientation = (q * self.orientation).normalized();
+                } else {
+                    // each cell is 2 dots wide and 4 dots high
+                    self.pan += Vector3::new(dx * 2.0, dy * 4.0, 0.0);
+                }
+
+                true
+            }
+        }
+    }
+
+    fn camera(&self, config: &App, scale: f32, dot_size: f32) -> Camera {
+        // moving the camera in the opposite direction makes the mesh move
+        // like it was panned.
+        scene_camera(config, self.bounds.radius * scale, -self.pan * dot_size)
+    }
+
+    fn status(&self) -> String {
+        let (axis, angle) = self.orientation.axis_angle();
+
+        format!(
+            "rotation: {:.0}° around ({:.2}, {:.2}, {:.2}) | step: {:.0}° | zoom: {:.2}x",
+            angle.to_degrees(),
+            axis.x,
+            axis.y,
+            axis.z,
+            self.rotation_step().to_degrees(),
+            self.zoom
+        )
+    }
+}
+
 trait Scene: Clone {
-    fn vertices<'s>(&'s self) -> Box<dyn Iterator<Item = &Vector3> + 's>;
-    fn vertices_mut<'s>(&'s mut self) -> Box<dyn Iterator<Item = &mut Vector3> + 's>;
-    fn render(&self, canvas: &mut Canvas, only_wireframe: bool);
+    fn vertices<'s>(&'s self) -> Box<dyn Iterator<Item = &'s Vector3> + 's>;
+    fn vertices_mut<'s>(&'s mut self) -> Box<dyn Iterator<Item = &'s mut Vector3> + 's>;
+    fn render(&self, canvas: &mut Canvas, camera: &Camera, config: &App);
 }
 
-impl Scene for Stl {
-    fn vertices<'s>(&'s self) -> Box<dyn Iterator<Item = &Vector3> + 's> {
-        Box::new(self.vertices())
+// A mesh along with its edges, that are found once when loading it because
+// they don't change while the mesh is transformed. The edges are shared by all
+// the copies of the scene that are rendered.
+#[derive(Debug, Clone)]
+struct MeshScene {
+    mesh: IndexedMesh,
+    edges: Arc<[Edge]>,
+}
+
+impl MeshScene {
+    fn new(mesh: IndexedMesh) -> Self {
+        let edges = mesh.edges().into();
+        MeshScene { mesh, edges }
     }
+}
 
-    fn vertices_mut<'s>(&'s mut self) -> Box<dyn Iterator<Item = &mut Vector3> + 's> {
-        Box::new(self.vertices_mut())
+impl Scene for MeshScene {
+    fn vertices<'s>(&'s self) -> Box<dyn Iterator<Item = &'s Vector3> + 's> {
+        Box::new(self.mesh.vertices.iter())
+    }
+
+    fn vertices_mut<'s>(&'s mut self) -> Box<dyn Iterator<Item = &'s mut Vector3> + 's> {
+        Box::new(self.mesh.vertices.iter_mut())
     }
 
-    fn render(&self, canvas: &mut Canvas, only_wireframe: bool) {
-        if only_wireframe {
-            for f in &self.facets {
-                canvas.triangle(f.vertices[0], f.vertices[1], f.vertices[2]);
+    fn render(&self, canvas: &mut Canvas, camera: &Camera, config: &App) {
+        let mesh = &self.mesh;
+        let project = camera.projector();
+        let view = camera.view_matrix();
+
+        let projected = par_map(mesh.vertices.len(), |i| project(mesh.vertices[i]));
+        let in_front = mesh
+            .vertices
+            .iter()
+            .map(|&v| camera.sees(v))
+            .collect::<Vec<_>>();
+
+        if config.only_wireframe || config.only_edges {
+            render_lines(
+                mesh,
+                &self.edges,
+                canvas,
+                camera,
+                &projected,
+                &in_front,
+                config,
+            );
+            return;
+        }
+
+        let corner_normals = if config.shading == Shading::Smooth {
+            mesh.corner_normals(config.crease_angle.to_radians())
+        } else {
+            vec![]
+        };
+
+        // The triangle to fill along with the shades of its vertices, `None`
+        // if it's culled. The vertices come with their barycentric
+        // coordinates in the facet `f` to interpolate the shades of its
+        // corners.
+        let shade = |f: usize, [a, b, c]: [(Vector3, [f32; 3]); 3]| {
+            // facets are wound counterclockwise when looking at their front,
+            // remember that the Y axis of the canvas points down.
+            if !config.no_cull && (b.0 - a.0).cross(&(c.0 - a.0)).z > 0.0 {
+                return None;
+            }
+
+            let shades = match config.shading {
+                Shading::Depth => None,
+                Shading::Flat => {
+                    let shade = lambert(view.transform_vector(mesh.face_normal(f)), config.light);
+                    Some([shade; 3])
+                }
+                Shading::Smooth => {
+                    let shades =
+                        corner_normals[f].map(|n| lambert(view.transform_vector(n), config.light));
+                    let shade =
+                        |w: [f32; 3]| w[0] * shades[0] + w[1] * shades[1] + w[2] * shades[2];
+                    Some([shade(a.1), shade(b.1), shade(c.1)])
+                }
+            };
+
+            Some(([a.0, b.0, c.0], shades))
+        };
+
+        // a facet clipped to the near plane is split in two triangles at most
+        let facet_triangles = |f: usize| {
+            let [a, b, c] = mesh.triangles[f];
+            if in_front[a] && in_front[b] && in_front[c] {
+                let [wa, wb, wc] = CORNERS;
+                let triangle = [(projected[a], wa), (projected[b], wb), (projected[c], wc)];
+                return [shade(f, triangle), None];
             }
+
+            let facet = [mesh.vertices[a], mesh.vertices[b], mesh.vertices[c]];
+            let mut triangles = [None, None];
+            for (t, triangle) in triangles.iter_mut().zip(clip_facet(camera, facet)) {
+                *t = shade(f, triangle);
+            }
+            triangles
+        };
+
+        let triangles = par_map(mesh.triangles.len(), facet_triangles)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect::<Vec<_>>();
+        canvas.fill_triangles(&triangles);
+    }
+}
+
+// Draw either the wireframe or only the edges of the mesh whose vertices are
+// already projected. The facets hide the lines behind them unless hidden lines
+// are shown.
+fn render_lines(
+    mesh: &IndexedMesh,
+    edges: &[Edge],
+    canvas: &mut Canvas,
+    camera: &Camera,
+    projected: &[Vector3],
+    in_front: &[bool],
+    config: &App,
+) {
+    // the facet `f` split in projected triangles in front of the camera
+    let facet = |f: usize| {
+        let [a, b, c] = mesh.triangles[f];
+
+        if in_front[a] && in_front[b] && in_front[c] {
+            vec![[projected[a], projected[b], projected[c]]]
+        } else {
+            let facet = [mesh.vertices[a], mesh.vertices[b], mesh.vertices[c]];
+            clip_facet(camera, facet)
+                .into_iter()
+                .map(|t| t.map(|(p, _)| p))
+                .collect()
+        }
+    };
+
+    // facets are wound counterclockwise when looking at their front, that is
+    // this is negative, remember that the Y axis of the canvas points down.
+    let facing = |f: usize| {
+        let [a, b, c] = mesh.triangles[f];
+        let [a, b, c] = if in_front[a] && in_front[b] && in_front[c] {
+            [projected[a], projected[b], projected[c]]
+        } else {
+            match facet(f).first() {
+                None => return 0.0,
+                Some(&t) => t,
+            }
+        };
+
+        (b - a).cross(&(c - a)).z
+    };
+
+    // the edge between the vertices `a` and `b` clipped and projected
+    let clip_edge = |a: usize, b: usize| {
+        if in_front[a] && in_front[b] {
+            Some((projected[a], projected[b]))
         } else {
-            for f in &self.facets {
-                canvas.fill_triangle(f.vertices[0], f.vertices[1], f.vertices[2]);
+            clip_line(camera, mesh.vertices[a], mesh.vertices[b])
+        }
+    };
+
+    if config.hidden_lines != HiddenLines::Show {
+        for f in 0..mesh.triangles.len() {
+            if config.no_cull || facing(f) <= 0.0 {
+                for [a, b, c] in facet(f) {
+                    occlude(canvas, a, b, c);
+                }
+            }
+        }
+    } else if config.only_wireframe {
+        for &[a, b, c] in &mesh.triangles {
+            if in_front[a] && in_front[b] && in_front[c] {
+                canvas.triangle(projected[a], projected[b], projected[c]);
+                continue;
+            }
+
+            for (p0, p1) in [clip_edge(a, b), clip_edge(b, c), clip_edge(c, a)]
+                .iter()
+                .flatten()
+            {
+                canvas.line(*p0, *p1);
+            }
+        }
+        return;
+    }
+
+    let min_cos = config.crease_angle.to_radians().cos();
+
+    for edge in edges {
+        let draw = config.only_wireframe
+            || match edge.faces[..] {
+                [f, g] => {
+                    let (front_f, front_g) = (facing(f) < 0.0, facing(g) < 0.0);
+                    let sharp = || {
+                        let (nf, ng) = (mesh.face_normal(f), mesh.face_normal(g));
+                        nf.normalized().dot(&ng.normalized()) < min_cos
+                    };
+
+                    // the silhouette is where the facets turn away from the
+                    // camera, the sharp edges behind the mesh would only add
+                    // clutter.
+                    front_f != front_g || (front_f && sharp())
+                }
+                // boundaries and non manifold edges
+                _ => true,
+            };
+
+        if draw {
+            let [a, b] = edge.vertices;
+            if let Some((p0, p1)) = clip_edge(a, b) {
+                canvas.hidden_line(p0, p1, config.hidden_lines);
             }
         }
     }
 }
 
 impl<'input> Scene for dsl::ast::Module<'input> {
-    fn vertices<'s>(&'s self) -> Box<dyn Iterator<Item = &Vector3> + 's> {
+    fn vertices<'s>(&'s self) -> Box<dyn Iterator<Item = &'s Vector3> + 's> {
         Box::new(self.vertices())
     }
 
-    fn vertices_mut<'s>(&'s mut self) -> Box<dyn Iterator<Item = &mut Vector3> + 's> {
+    fn vertices_mut<'s>(&'s mut self) -> Box<dyn Iterator<Item = &'s mut Vector3> + 's> {
         Box::new(self.vertices_mut())
     }
 
-    fn render(&self, canvas: &mut Canvas, only_wireframe: bool) {
+    fn render(&self, canvas: &mut Canvas, camera: &Camera, config: &App) {
+        let project = camera.projector();
+        let view = camera.view_matrix();
+        let lines_only = config.only_wireframe || config.only_edges;
+        let hidden = lines_only && config.hidden_lines != HiddenLines::Show;
+
+        // vertices come both in world space and projected, the projected ones
+        // can be used as is only if they're in front of the camera.
+        let segment = |(a, pa): (Vector3, Vector3), (b, pb): (Vector3, Vector3)| {
+            if camera.sees(a) && camera.sees(b) {
+                Some((pa, pb))
+            } else {
+                clip_line(camera, a, b)
+            }
+        };
+        let triangles = |(a, pa): (Vector3, Vector3),
+                         (b, pb): (Vector3, Vector3),
+                         (c, pc): (Vector3, Vector3)| {
+            if camera.sees(a) && camera.sees(b) && camera.sees(c) {
+                vec![[pa, pb, pc]]
+            } else {
+                clip_facet(camera, [a, b, c])
+                    .into_iter()
+                    .map(|t| t.map(|(p, _)| p))
+                    .collect()
+            }
+        };
+
+        // the triangles must hide the lines behind them before any line is
+        // drawn, in any order.
+        if hidden {
+            let mut env = std::collections::HashMap::new();
+
+            for stmt in &self.statements {
+                match stmt.expr {
+                    termesh::dsl::ast::Expr::Vertex(name, pos) => {
+                        env.insert(name, (pos, project(pos)));
+                    }
+                    termesh::dsl::ast::Expr::Line(_, _) => {}
+                    termesh::dsl::ast::Expr::Triangle(v0, v1, v2) => {
+                        for [a, b, c] in triangles(env[v0], env[v1], env[v2]) {
+                            occlude(canvas, a, b, c);
+                        }
+                    }
+                }
+            }
+        }
+
         let mut env = std::collections::HashMap::new();
 
         for stmt in &self.statements {
             match stmt.expr {
                 termesh::dsl::ast::Expr::Vertex(name, pos) => {
-                    env.insert(name, pos);
+                    env.insert(name, (pos, project(pos)));
                 }
                 termesh::dsl::ast::Expr::Line(v0, v1) => {
-                    canvas.line(env[v0], env[v1]);
+                    let (p0, p1) = match segment(env[v0], env[v1]) {
+                        None => continue,
+                        Some(line) => line,
+                    };
+
+                    if hidden {
+                        canvas.hidden_line(p0, p1, config.hidden_lines);
+                    } else {
+                        canvas.line(p0, p1);
+                    }
                 }
                 termesh::dsl::ast::Expr::Triangle(v0, v1, v2) => {
-                    if only_wireframe {
-                        canvas.triangle(env[v0], env[v1], env[v2]);
-                    } else {
-                        canvas.fill_triangle(env[v0], env[v1], env[v2]);
+                    let (a, b, c) = (env[v0].0, env[v1].0, env[v2].0);
+                    let in_front = camera.sees(a) && camera.sees(b) && camera.sees(c);
+
+                    // triangles in the DSL have no neighbours, so all their
+                    // edges are boundaries
+                    if lines_only && !hidden && in_front {
+                        canvas.triangle(env[v0].1, env[v1].1, env[v2].1);
+                        continue;
+                    }
+                    if lines_only {
+                        let edges = [
+                            segment(env[v0], env[v1]),
+                            segment(env[v1], env[v2]),
+                            segment(env[v2], env[v0]),
+                        ];
+
+                        for &(p0, p1) in edges.iter().flatten() {
+                            if hidden {
+                                canvas.hidden_line(p0, p1, config.hidden_lines);
+                            } else {
+                                canvas.line(p0, p1);
+                            }
+                        }
+                        continue;
+                    }
+
+                    for [pa, pb, pc] in triangles(env[v0], env[v1], env[v2]) {
+                        match config.shading {
+                            Shading::Depth => canvas.fill_triangle(pa, pb, pc),
+                            Shading::Flat | Shading::Smooth => {
+                                // triangles in the DSL have no particular
+                                // winding so light them from both sides. They
+                                // also don't share vertices, hence there's
+                                // nothing to smooth.
+                                let mut normal = view.transform_vector((b - a).cross(&(c - a)));
+                                if normal.z > 0.0 {
+                                    normal = -normal;
+                                }
+
+                                let shade = lambert(normal, config.light);
+                                canvas.fill_triangle_shaded(pa, pb, pc, [shade; 3]);
+                            }
+                        }
                     }
                 }
             }
@@ -147,11 +807,7 @@ fn main() -> io::Result<()> {
                         exit(1);
                     }
 
-                    if app.non_interactive || !termion::is_tty(&io::stdout()) {
-                        non_interactive(app, prog)?;
-                    } else {
-                        interactive(app, prog)?;
-                    }
+                    run(app, prog)?;
                 }
                 Err(parse_error) => {
                     eprintln!();
@@ -164,12 +820,36 @@ fn main() -> io::Result<()> {
         }
     }
 
-    let stl = Stl::parse_binary(&mut f)?;
+    let mut stl = Stl::parse_binary(&mut f)?;
 
-    if app.non_interactive || !termion::is_tty(&io::stdout()) {
-        non_interactive(app, stl)
+    let removed = stl.remove_non_finite();
+    if removed > 0 {
+        print_warning(format!(
+            "skipped {} facets with NaN or infinite vertices",
+            removed
+        ));
+    }
+
+    run(app, MeshScene::new(IndexedMesh::from(&stl)))
+}
+
+fn run<S: Scene>(config: App, scene: S) -> io::Result<()> {
+    if scene.vertices().next().is_none() {
+        print_warning("the mesh is empty, there's nothing to display");
+    }
+
+    // the camera can't be placed around a mesh whose size overflows
+    let pivot = pivot_point(&scene, config.pivot);
+    let radius = BoundingSphere::around(pivot, scene.vertices()).radius;
+    if !(radius * config.scale.unwrap_or(1.0)).is_finite() {
+        print_error("the mesh is too big to be displayed at this scale");
+        exit(1);
+    }
+
+    if config.non_interactive || !termion::is_tty(&io::stdout()) {
+        non_interactive(config, scene)
     } else {
-        interactive(app, stl)
+        interactive(config, scene)
     }
 }
 
@@ -177,85 +857,223 @@ fn non_interactive<S: Scene>(config: App, mut scene: S) -> io::Result<()> {
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
 
-    rotate_scene(
-        &mut scene,
-        config.rotation_x,
-        config.rotation_y,
-        config.rotation_z,
-    );
-    scale_scene(&mut scene, config.scale.unwrap_or(1.0));
+    let scale = config.scale.unwrap_or(1.0) * dot_size(graphics(&config));
+    let pivot = pivot_point(&scene, config.pivot);
+    let transform = Matrix4::scale(scale) * rotation_matrix(&config) * Matrix4::translation(-pivot);
+    transform_scene(&mut scene, &transform);
 
-    render_scene(&mut stdout, &scene, false, None, &config)?;
+    // the pivot is at the origin after the transformation
+    let origin = Vector3::new(0.0, 0.0, 0.0);
+    let bounds = BoundingSphere::around(origin, scene.vertices());
+    let camera = scene_camera(&config, bounds.radius, origin);
+
+    let cut_off = camera.half_height() > MAX_OUTPUT_DOT as f32;
+    if cut_off {
+        print_warning("the mesh is too big to be displayed whole at this scale, it's cut off");
+    }
+
+    let frame = render_scene(&mut stdout, &scene, &camera, None, None, &config)?;
+
+    // the parts of the mesh around the pivot may all be cut off
+    if cut_off && frame.is_empty() {
+        print_error("nothing of the mesh is left to display at this scale");
+        exit(1);
+    }
 
     Ok(())
 }
 
 fn interactive<S: Scene>(mut config: App, scene: S) -> io::Result<()> {
-    let mut stdout = io::stdout().into_raw_mode()?;
-    write!(stdout, "{}\r\n", termion::cursor::Hide)?;
+    let mut stdout = MouseTerminal::from(io::stdout().into_raw_mode()?.into_alternate_screen()?);
+    write!(stdout, "{}", termion::cursor::Hide)?;
+
+    let zoom_inc = 1.25;
 
-    let angle_inc = PI / 6.0;
+    // pan by the same amount of dots, that is 4 cells horizontally and 2
+    // vertically.
+    let pan_inc = 8.0;
 
-    let mut draw = |c: &App, mut scene| -> io::Result<Vec<String>> {
+    let light_step = PI / 12.0;
+
+    let x_axis = Vector3::new(1.0, 0.0, 0.0);
+    let y_axis = Vector3::new(0.0, 1.0, 0.0);
+    let z_axis = Vector3::new(0.0, 0.0, 1.0);
+
+    let mut view = View::new(&scene, &config);
+    let mut screen = Screen::new();
+    let mut last_terminal_size = None;
+
+    let mut draw = |c: &App, view: &View, mut scene| -> io::Result<Vec<String>> {
         let terminal_size = termion::terminal_size()?;
 
-        rotate_scene(&mut scene, c.rotation_x, c.rotation_y, c.rotation_z);
+        // what's on screen is all over the place after a resize
+        if last_terminal_size != Some(terminal_size) {
+            last_terminal_size = Some(terminal_size);
+            screen.invalidate();
+        }
 
         let padding = 5;
         let scale = c.scale.unwrap_or_else(|| {
-            determine_scale_factor(&scene, terminal_size.0 - padding, terminal_size.1 - padding)
+            determine_scale_factor(
+                view.bounds.radius,
+                terminal_size.0 - padding,
+                terminal_size.1 - padding,
+            )
         });
 
-        scale_scene(&mut scene, scale);
-        render_scene(
+        let dot_size = dot_size(graphics(c));
+        let scale = scale * view.zoom * dot_size;
+        let transform = Matrix4::scale(scale)
+            * view.orientation.to_matrix()
+            * Matrix4::translation(-view.bounds.center);
+
+        transform_scene(&mut scene, &transform);
+        let frame = render_scene(
             &mut stdout,
             &scene,
-            true,
+            &view.camera(c, scale, dot_size),
+            Some(&mut screen),
             Some((i32::from(terminal_size.0), i32::from(terminal_size.1))),
             c,
-        )
+        )?;
+
+        screen.set_row(
+            usize::from(terminal_size.1).saturating_sub(1),
+            view.status()
+                .chars()
+                .take(usize::from(terminal_size.0))
+                .map(Cell::new)
+                .collect(),
+        );
+        screen.present(&mut stdout)?;
+
+        Ok(frame)
     };
 
-    let mut current_frame = draw(&config, scene.clone())?;
+    let mut current_frame = draw(&config, &view, scene.clone())?;
 
-    for ev in io::stdin().keys() {
+    for ev in io::stdin().events() {
         let ev = ev?;
 
         let redraw = match ev {
-            termion::event::Key::Char('q') => break,
-            termion::event::Key::Char('x') => {
-                config.rotation_x = (config.rotation_x + angle_inc) % (2.0 * PI);
+            Event::Key(Key::Char('q')) => break,
+            Event::Key(Key::Char('x')) => {
+                view.rotate(x_axis, 1.0);
+                true
+            }
+            Event::Key(Key::Char('X')) => {
+                view.rotate(x_axis, -1.0);
                 true
             }
-            termion::event::Key::Char('X') => {
-                config.rotation_x = (config.rotation_x - angle_inc) % (2.0 * PI);
+            Event::Key(Key::Char('y')) => {
+                view.rotate(y_axis, 1.0);
                 true
             }
-            termion::event::Key::Char('y') => {
-                config.rotation_y = (config.rotation_y + angle_inc) % (2.0 * PI);
+            Event::Key(Key::Char('Y')) => {
+                view.rotate(y_axis, -1.0);
                 true
             }
-            termion::event::Key::Char('Y') => {
-                config.rotation_y = (config.rotation_y - angle_inc) % (2.0 * PI);
+            Event::Key(Key::Char('z')) => {
+                view.rotate(z_axis, 1.0);
                 true
             }
-            termion::event::Key::Char('z') => {
-                config.rotation_z = (config.rotation_z + angle_inc) % (2.0 * PI);
+            Event::Key(Key::Char('Z')) => {
+                view.rotate(z_axis, -1.0);
                 true
             }
-            termion::event::Key::Char('Z') => {
-                config.rotation_z = (config.rotation_z - angle_inc) % (2.0 * PI);
+            Event::Key(Key::Char('f')) => {
+                view.fine_steps = !view.fine_steps;
                 true
             }
-            termion::event::Key::Char('w') => {
+            Event::Key(Key::Char('w')) => {
                 config.only_wireframe = !config.only_wireframe;
+                config.only_edges = false;
+                true
+            }
+            Event::Key(Key::Char('v')) => {
+                config.hidden_lines = config.hidden_lines.next();
                 true
             }
-            termion::event::Key::Char('d') => {
+            Event::Key(Key::Char('e')) => {
+                config.only_edges = !config.only_edges;
+                config.only_wireframe = false;
+                true
+            }
+            Event::Key(Key::Char('m')) => {
+                config.shading = config.shading.next();
+                true
+            }
+            Event::Key(Key::Char('h')) => {
+                config.light = Quaternion::from_axis_angle(y_axis, light_step).rotate(config.light);
+                true
+            }
+            Event::Key(Key::Char('l')) => {
+                config.light =
+                    Quaternion::from_axis_angle(y_axis, -light_step).rotate(config.light);
+                true
+            }
+            Event::Key(Key::Char('k')) => {
+                config.light =
+                    Quaternion::from_axis_angle(x_axis, -light_step).rotate(config.light);
+                true
+            }
+            Event::Key(Key::Char('j')) => {
+                config.light = Quaternion::from_axis_angle(x_axis, light_step).rotate(config.light);
+                true
+            }
+            Event::Key(Key::Char('b')) => {
+                config.no_cull = !config.no_cull;
+                true
+            }
+            Event::Key(Key::Char('g')) => {
+                config.charset = config.charset.next();
+                true
+            }
+            Event::Key(Key::Char('o')) => {
+                config.dither = !config.dither;
+                true
+            }
+            Event::Key(Key::Char('d')) => {
                 config.no_depth = !config.no_depth;
                 true
             }
-            termion::event::Key::Char('s') => {
+            Event::Key(Key::Char('p')) => {
+                config.perspective = !config.perspective;
+                true
+            }
+            Event::Key(Key::Char('c')) => {
+                view.recenter(&scene, config.pivot);
+                true
+            }
+            Event::Key(Key::Char('+')) | Event::Key(Key::Char('=')) => {
+                view.zoom *= zoom_inc;
+                true
+            }
+            Event::Key(Key::Char('-')) => {
+                view.zoom /= zoom_inc;
+                true
+            }
+            Event::Key(Key::Char('0')) => {
+                view.fit();
+                true
+            }
+            Event::Key(Key::Left) => {
+                view.pan.x -= pan_inc;
+                true
+            }
+            Event::Key(Key::Right) => {
+                view.pan.x += pan_inc;
+                true
+            }
+            Event::Key(Key::Up) => {
+                view.pan.y -= pan_inc;
+                true
+            }
+            Event::Key(Key::Down) => {
+                view.pan.y += pan_inc;
+                true
+            }
+            Event::Key(Key::Char('s')) => {
                 if let Err(err) = save_frame(&config, &current_frame) {
                     reset_screen(&mut stdout)?;
                     return Err(err);
@@ -263,11 +1081,12 @@ fn interactive<S: Scene>(mut config: App, scene: S) -> io::Result<()> {
 
                 false
             }
+            Event::Mouse(mouse_ev) => view.handle_mouse(mouse_ev),
             _ => continue,
         };
 
         if redraw {
-            current_frame = draw(&config, scene.clone())?;
+            current_frame = draw(&config, &view, scene.clone())?;
         }
     }
 
@@ -276,43 +1095,101 @@ fn interactive<S: Scene>(mut config: App, scene: S) -> io::Result<()> {
     Ok(())
 }
 
+// Render the scene and write it out, unless there's a `screen` in which case
+// the rows of the frame are only set on the screen and it's up to the caller
+// to present it.
 fn render_scene<W: Write, S: Scene>(
     w: &mut W,
     scene: &S,
-    clear: bool,
+    camera: &Camera,
+    mut screen: Option<&mut Screen>,
     max_dimensions: Option<(i32, i32)>,
     config: &App,
 ) -> io::Result<Vec<String>> {
-    let mut canvas = Canvas::new();
-
-    scene.render(&mut canvas, config.only_wireframe);
-
-    // callers can clear the screen by themselves, but it usually causes
-    // flickering on big terminals. Therefore defer clearing the screen until
-    // the very last.
-    if clear {
-        // changing the background color needs clearing before it can be
-        // rendered effectively
-        if !config.no_depth {
-            write!(
-                w,
-                "{}",
-                termion::color::Bg(termesh::drawille::Canvas::background_color())
-            )?;
-        } else {
-            write!(
-                w,
-                "{}{}",
-                termion::color::Bg(termion::color::Reset),
-                termion::color::Fg(termion::color::Reset)
-            )?;
+    let protocol = graphics(config);
+
+    // only the window shown on screen is ever rendered when interactive,
+    // everything else can be clipped.
+    let mut canvas = match (max_dimensions, protocol) {
+        (None, _) => {
+            let mut canvas = Canvas::new();
+            canvas.set_max_dot(max_dot(camera));
+            canvas
+        }
+        (Some((max_width, max_height)), None) => {
+            let (min_r, max_r, min_c, max_c) = frame_window(max_width, max_height);
+            Canvas::with_viewport(min_r, max_r, min_c, max_c)
+        }
+        (Some((max_width, max_height)), Some(_)) => {
+            let (min_x, min_y, width, height) = image_window(max_width, max_height);
+            let (min_c, min_r) = Canvas::pos(min_x as f32, min_y as f32);
+            let (max_c, max_r) =
+                Canvas::pos((min_x + width - 1) as f32, (min_y + height - 1) as f32);
+
+            Canvas::with_viewport(min_r, max_r, min_c, max_c)
         }
-        clear_screen(w)?;
+    };
+    canvas.set_charset(config.charset);
+    canvas.set_dither(config.dither);
+
+    let palette = palette(config).map(|mut palette| {
+        // images are not limited to the colors of the terminal
+        if protocol.is_some() {
+            palette.depth = ColorDepth::TrueColor;
+        }
+        palette
+    });
+    let with_colors = palette.is_some();
+    if let Some(palette) = palette {
+        canvas.set_palette(palette);
+    }
+    let painted_background = screen.is_some() && with_colors;
+    canvas.set_painted_background(painted_background);
+
+    scene.render(&mut canvas, camera, config);
+
+    let background = if painted_background {
+        Some(canvas.background_color())
+    } else {
+        None
+    };
+    if let Some(screen) = &mut screen {
+        screen.set_background(background);
+    }
+
+    if let Some(protocol) = protocol {
+        // the screen doesn't know about images, start from a blank one
+        if let Some(screen) = &mut screen {
+            screen.clear(w)?;
+        }
+
+        // the canvas is in pixels, that is every dot is a pixel of the image
+        let (min_x, min_y, width, height) = match max_dimensions {
+            None => canvas
+                .dimensions()
+                .map_or((0, 0, 0, 0), |(min_r, max_r, min_c, max_c)| {
+                    (
+                        min_c * 2,
+                        min_r * 4,
+                        (max_c - min_c + 1) * 2,
+                        (max_r - min_r + 1) * 4,
+                    )
+                }),
+            Some((max_width, max_height)) => image_window(max_width, max_height),
+        };
+
+        let image = canvas.image(with_colors, min_x, min_y, width as usize, height as usize);
+        let frame = vec![format!("{}{}", protocol.clear(), protocol.encode(&image))];
+
+        write!(w, "{}\r\n", frame[0])?;
+        w.flush()?;
+
+        return Ok(frame);
     }
 
     let frame = match max_dimensions {
         None => {
-            let frame = canvas.rows(!config.no_depth).collect::<Vec<_>>();
+            let frame = canvas.rows(with_colors).collect::<Vec<_>>();
 
             for r in &frame {
                 write!(w, "{}\r\n", r)?;
@@ -322,31 +1199,30 @@ fn render_scene<W: Write, S: Scene>(
             frame
         }
         Some((max_width, max_height)) => {
-            if let Some((min_r, max_r, min_c, max_c)) = canvas.dimensions() {
-                let padded = |min, max, max_len| {
-                    if max_len <= max - min {
-                        min
-                    } else {
-                        let padding = max_len - (max - min);
-                        min - padding / 2
-                    }
-                };
+            let (min_r, max_r, min_c, max_c) = frame_window(max_width, max_height);
+            let rows = canvas.frame(with_colors, min_r, max_r, min_c, Some(max_c));
 
-                let min_r = padded(min_r, max_r, max_height);
-                let min_c = padded(min_c, max_c, max_width);
+            match screen {
+                None => {
+                    let frame = rows.collect::<Vec<_>>();
 
-                let frame = canvas
-                    .frame(!config.no_depth, min_r, max_r, min_c, Some(max_c))
-                    .collect::<Vec<_>>();
+                    for r in &frame {
+                        write!(w, "{}\r\n", r)?;
+                    }
+                    w.flush()?;
 
-                for r in &frame {
-                    write!(w, "{}\r\n", r)?;
+                    frame
                 }
-                w.flush()?;
+                Some(screen) => {
+                    let frame = rows.into_frame();
+                    let ansi = frame.to_ansi();
 
-                frame
-            } else {
-                vec![]
+                    for (r, cells) in frame.rows.into_iter().enumerate() {
+                        screen.set_row(r, cells);
+                    }
+
+                    ansi
+                }
             }
         }
     };
@@ -354,59 +1230,203 @@ fn render_scene<W: Write, S: Scene>(
     Ok(frame)
 }
 
-fn rotate_scene<S: Scene>(scene: &mut S, rotation_x: f32, rotation_y: f32, rotation_z: f32) {
-    if rotation_x == 0.0 && rotation_y == 0.0 && rotation_z == 0.0 {
+// The cells of the canvas shown on a screen of the given size as (min row, max
+// row, min column, max column). The pivot is always projected at the origin,
+// center the frame around it. Leave the last row empty to not scroll the
+// screen.
+fn frame_window(max_width: i32, max_height: i32) -> (i32, i32, i32, i32) {
+    let min_r = -(max_height - 1) / 2;
+    let min_c = -max_width / 2;
+
+    (min_r, min_r + max_height - 2, min_c, min_c + max_width - 1)
+}
+
+// Like `frame_window`, but in pixels as (min x, min y, width, height) for the
+// image shown with a graphics protocol.
+fn image_window(max_width: i32, max_height: i32) -> (i32, i32, i32, i32) {
+    let (cell_width, cell_height) = cell_pixels();
+    let width = max_width * cell_width;
+    let height = (max_height - 1) * cell_height;
+
+    (-width / 2, -height / 2, width, height)
+}
+
+// rotations are applied around the X axis first, then around the Y axis and
+// finally around the Z axis.
+fn rotation_matrix(config: &App) -> Matrix4 {
+    Matrix4::rotation_z(config.rotation_z)
+        * Matrix4::rotation_y(config.rotation_y)
+        * Matrix4::rotation_x(config.rotation_x)
+}
+
+fn pivot_point<S: Scene>(scene: &S, pivot: Pivot) -> Vector3 {
+    let p = match pivot {
+        Pivot::Center => BoundingBox::from_points(scene.vertices()).map(|bbox| bbox.center()),
+        Pivot::Centroid => bounds::centroid(scene.vertices()),
+        Pivot::Point(p) => Some(p),
+    };
+
+    p.unwrap_or_else(|| Vector3::new(0.0, 0.0, 0.0))
+}
+
+fn transform_scene<S: Scene>(scene: &mut S, transform: &Matrix4) {
+    if *transform == Matrix4::identity() {
         return;
     }
 
     for v in scene.vertices_mut() {
-        if rotation_x != 0.0 {
-            v.rotate_x(rotation_x);
-        }
+        *v = transform.transform_point(*v);
+    }
+}
+
+// camera looking at the given target that fits a sphere of the given radius
+fn scene_camera(config: &App, radius: f32, target: Vector3) -> Camera {
+    let mut camera = Camera::new();
+
+    camera.target = target;
+    camera.position = target + Vector3::new(0.0, 0.0, -1.0);
+    camera.fov = config.fov.to_radians();
+    if config.perspective {
+        camera.projection = Projection::Perspective;
+    }
+
+    camera.fit(radius);
+
+    camera
+}
 
-        if rotation_y != 0.0 {
-            v.rotate_y(rotation_y);
+// The coordinates of the dots of the canvas when not interactive are at most
+// this big, so that huge scales don't take forever to render.
+const MAX_OUTPUT_DOT: i32 = 1 << 12;
+
+// The limit of the coordinates of the dots the scene can be drawn on when the
+// canvas is not limited to the screen, see `Canvas::set_max_dot`. The whole
+// visible area of the camera is drawn.
+fn max_dot(camera: &Camera) -> i32 {
+    (camera.half_height().ceil() as i32)
+        .saturating_add(1)
+        .min(MAX_OUTPUT_DOT)
+}
+
+// the protocol to render the scene as an image with, `None` to use characters
+fn graphics(config: &App) -> Option<Protocol> {
+    match config.graphics {
+        GraphicsChoice::Never => None,
+        GraphicsChoice::Auto => Protocol::detect(),
+        GraphicsChoice::Sixel => Some(Protocol::Sixel),
+        GraphicsChoice::Kitty => Some(Protocol::Kitty),
+    }
+}
+
+// Size in pixels of a cell of the terminal, if the terminal doesn't tell then
+// assume a common one.
+fn cell_pixels() -> (i32, i32) {
+    match (termion::terminal_size(), termion::terminal_size_pixels()) {
+        (Ok((cols, rows)), Ok((width, height)))
+            if cols > 0 && rows > 0 && width >= cols && height >= rows =>
+        {
+            (i32::from(width / cols), i32::from(height / rows))
         }
+        _ => (8, 16),
+    }
+}
 
-        if rotation_z != 0.0 {
-            v.rotate_z(rotation_z);
+// How many units of the canvas make a braille dot, that is how many pixels
+// when rendering an image so that the mesh keeps the same size on the screen.
+fn dot_size(protocol: Option<Protocol>) -> f32 {
+    match protocol {
+        None => 1.0,
+        Some(_) => {
+            let (cell_width, cell_height) = cell_pixels();
+            (cell_width as f32 / 2.0).min(cell_height as f32 / 4.0)
         }
     }
 }
 
-fn scale_scene<S: Scene>(scene: &mut S, scale: f32) {
-    if scale == 1.0 {
-        return;
+// the palette to render the scene with, `None` if colors must not be used
+fn palette(config: &App) -> Option<Palette> {
+    if config.no_depth {
+        return None;
     }
 
-    for v in scene.vertices_mut() {
-        *v *= scale;
-    }
-}
-
-fn determine_scale_factor<S: Scene>(scene: &S, max_width: u16, max_height: u16) -> f32 {
-    let mut vs = scene.vertices();
-
-    let (w, h) = vs
-        .next()
-        .map(|v| {
-            vs.fold((v.x, v.y, v.x, v.y), |(min_x, min_y, max_x, max_y), v| {
-                (
-                    min_x.min(v.x),
-                    min_y.min(v.y),
-                    max_x.max(v.x),
-                    max_y.max(v.y),
-                )
-            })
-        })
-        .map_or((1.0, 1.0), |(min_x, min_y, max_x, max_y)| {
-            (max_x - min_x, max_y - min_y)
-        });
+    let depth = match config.color {
+        ColorChoice::Never => return None,
+        ColorChoice::Auto => ColorDepth::detect()?,
+        ColorChoice::Always => ColorDepth::detect().unwrap_or(ColorDepth::Ansi256),
+    };
+
+    let mut palette = Palette::new(config.colormap.clone(), depth);
+    palette.light_background = config.light_background;
+
+    Some(palette)
+}
+
+// Hide what's behind the given facet. Its edges lie on it, push the facet back
+// a bit so that it doesn't hide them too.
+fn occlude(canvas: &mut Canvas, a: Vector3, b: Vector3, c: Vector3) {
+    let bias = Vector3::new(0.0, 0.0, 1.5);
+    canvas.occlude(a + bias, b + bias, c + bias);
+}
+
+// Call `f` on all the numbers in `0..n` collecting the results in order. With
+// the `rayon` feature the calls are spread across threads.
+#[cfg(feature = "rayon")]
+fn par_map<T: Send>(n: usize, f: impl Fn(usize) -> T + Sync + Send) -> Vec<T> {
+    use rayon::prelude::*;
+
+    (0..n).into_par_iter().map(f).collect()
+}
+
+#[cfg(not(feature = "rayon"))]
+fn par_map<T>(n: usize, f: impl Fn(usize) -> T) -> Vec<T> {
+    (0..n).map(f).collect()
+}
+
+// Barycentric coordinates of the corners of a triangle.
+const CORNERS: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+// The facet with the given vertices split in projected triangles that are in
+// front of the near plane of the camera, along with the barycentric
+// coordinates of their vertices in the facet.
+fn clip_facet(camera: &Camera, facet: [Vector3; 3]) -> Vec<[(Vector3, [f32; 3]); 3]> {
+    let project = camera.projector();
+    let polygon = camera.clip_triangle(facet);
+
+    (2..polygon.len())
+        .map(|i| [polygon[0], polygon[i - 1], polygon[i]].map(|(p, w)| (project(p), w)))
+        .collect()
+}
+
+// The segment between the given points clipped to the near plane of the
+// camera and projected, `None` if it's entirely behind it.
+fn clip_line(camera: &Camera, p0: Vector3, p1: Vector3) -> Option<(Vector3, Vector3)> {
+    let project = camera.projector();
+    camera
+        .clip_segment(p0, p1)
+        .map(|(p0, p1)| (project(p0), project(p1)))
+}
+
+// fraction of light received by a surface with the given normal when lit by a
+// light in the given direction, surfaces that face away from the light still
+// get some ambient light.
+fn lambert(normal: Vector3, light: Vector3) -> f32 {
+    let ambient = 0.15;
+    let diffuse = normal.normalized().dot(&light.normalized()).max(0.0);
+
+    ambient + (1.0 - ambient) * diffuse
+}
 
-    let scalex = f32::from(max_width) / w * 2.0;
-    let scaley = f32::from(max_height) / h * 4.0;
+// scale factor to fit a sphere with the given radius in a screen with the
+// given size. Meshes made of a single point, or none at all, fit any screen.
+fn determine_scale_factor(radius: f32, max_width: u16, max_height: u16) -> f32 {
+    if !(radius > 0.0 && radius.is_finite()) {
+        return 1.0;
+    }
+
+    let scalex = f32::from(max_width) * 2.0;
+    let scaley = f32::from(max_height) * 4.0;
 
-    scalex.min(scaley)
+    scalex.min(scaley) / (radius * 2.0)
 }
 
 fn save_frame(config: &App, frame: &[String]) -> io::Result<()> {
@@ -464,16 +1484,16 @@ fn print_dsl_error<T: std::fmt::Display>(err: dsl::ast::Error<T>, filepath: &Pat
     eprintln!();
 }
 
-fn clear_screen<W: Write>(w: &mut W) -> io::Result<()> {
-    write!(
-        w,
-        "{}{}\r\n",
-        termion::clear::All,
-        termion::cursor::Goto(1, 1),
-    )?;
-    w.flush()?;
+fn print_warning(msg: impl std::fmt::Display) {
+    use termion::color::{Fg, LightYellow, Reset};
 
-    Ok(())
+    eprintln!("{}warning{}: {}", Fg(LightYellow), Fg(Reset), msg);
+}
+
+fn print_error(msg: impl std::fmt::Display) {
+    use termion::color::{Fg, LightRed, Reset};
+
+    eprintln!("{}error{}: {}", Fg(LightRed), Fg(Reset), msg);
 }
 
 fn reset_screen<W: Write>(w: &mut W) -> io::Result<()> {
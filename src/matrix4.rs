@@ -0,0 +1,395 @@
+//! A simple 4x4 Matrix to represent affine and projective transformations.
+//!
+//! Points are treated as column vectors, therefore `a * b` is the
+//! transformation that applies `b` first and then `a`.
+//!
+//! The coordinate system follows the one of the canvas: the X axis points to
+//! the right, the Y axis points down and the Z axis points away from the
+//! viewer.
+
+use std::ops::Mul;
+
+use crate::{Quaternion, Vector3};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Matrix4 {
+    // row major
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    /// Create a new Matrix4 from the given rows
+    pub fn new(m: [[f32; 4]; 4]) -> Self {
+        Matrix4 { m }
+    }
+
+    /// The transformation that leaves points untouched
+    pub fn identity() -> Self {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Translate points by the given offset
+    pub fn translation(offset: Vector3) -> Self {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Uniformly scale points by the given factor
+    pub fn scale(factor: f32) -> Self {
+        Matrix4::new([
+            [factor, 0.0, 0.0, 0.0],
+            [0.0, factor, 0.0, 0.0],
+            [0.0, 0.0, factor, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotate points around the X axis by the given angle in radians
+    pub fn rotation_x(angle: f32) -> Self {
+        let (sina, cosa) = angle.sin_cos();
+
+        Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cosa, -sina, 0.0],
+            [0.0, sina, cosa, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotate points around the Y axis by the given angle in radians
+    pub fn rotation_y(angle: f32) -> Self {
+        let (sina, cosa) = angle.sin_cos();
+
+        Matrix4::new([
+            [cosa, 0.0, sina, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sina, 0.0, cosa, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotate points around the Z axis by the given angle in radians
+    pub fn rotation_z(angle: f32) -> Self {
+        let (sina, cosa) = angle.sin_cos();
+
+        Matrix4::new([
+            [cosa, -sina, 0.0, 0.0],
+            [sina, cosa, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotate points around the given axis by the given angle in radians
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        Quaternion::from_axis_angle(axis, angle).to_matrix()
+    }
+
+    /// The view transformation of a viewer placed at `eye` looking at
+    /// `target`. `up` is the direction that should point up on the screen.
+    ///
+    /// After the transformation the viewer is at the origin looking towards
+    /// the positive Z axis.
+    pub fn look_at(eye: Vector3, target: Vector3, up: Vector3) -> Self {
+        let forward = (target - eye).normalized();
+        let right = forward.cross(&up).normalized();
+        let down = forward.cross(&right);
+
+        Matrix4::new([
+            [right.x, right.y, right.z, -right.dot(&eye)],
+            [down.x, down.y, down.z, -down.dot(&eye)],
+            [forward.x, forward.y, forward.z, -forward.dot(&eye)],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Perspective projection with the given vertical field of view in
+    /// radians. Points in the view frustum are mapped in the [-1, 1] range on
+    /// the X and Y axes and in the [0, 1] range on the Z axis.
+    pub fn perspective(fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        let f = 1.0 / (fov / 2.0).tan();
+        let depth = far - near;
+
+        Matrix4::new([
+            [f / aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [0.0, 0.0, far / depth, -near * far / depth],
+            [0.0, 0.0, 1.0, 0.0],
+        ])
+    }
+
+    /// Orthographic projection of the given box. Points in the box are
+    /// mapped in the [-1, 1] range on the X and Y axes and in the [0, 1] range
+    /// on the Z axis.
+    pub fn orthographic(left: f32, right: f32, top: f32, bottom: f32, near: f32, far: f32) -> Self {
+        let width = right - left;
+        let height = bottom - top;
+        let depth = far - near;
+
+        Matrix4::new([
+            [2.0 / width, 0.0, 0.0, -(right + left) / width],
+            [0.0, 2.0 / height, 0.0, -(bottom + top) / height],
+            [0.0, 0.0, 1.0 / depth, -near / depth],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = [[0.0; 4]; 4];
+
+        for (r, row) in self.m.iter().enumerate() {
+            for (c, v) in row.iter().enumerate() {
+                t[c][r] = *v;
+            }
+        }
+
+        Matrix4::new(t)
+    }
+
+    /// The inverse transformation, `None` if the matrix is singular or not
+    /// finite.
+    pub fn inverse(&self) -> Option<Self> {
+        if !self.m.iter().flatten().all(|v| v.is_finite()) {
+            return None;
+        }
+
+        // plain Gauss-Jordan elimination with partial pivoting
+        let mut m = self.m;
+        let mut inv = Matrix4::identity().m;
+
+        for c in 0..4 {
+            let pivot = (c..4)
+                .max_by(|&r0, &r1| m[r0][c].abs().total_cmp(&m[r1][c].abs()))
+                .unwrap();
+
+            if m[pivot][c] == 0.0 {
+                return None;
+            }
+
+            m.swap(c, pivot);
+            inv.swap(c, pivot);
+
+            let p = m[c][c];
+            for i in 0..4 {
+                m[c][i] /= p;
+                inv[c][i] /= p;
+            }
+
+            for r in 0..4 {
+                if r == c {
+                    continue;
+                }
+
+                let f = m[r][c];
+                for i in 0..4 {
+                    m[r][i] -= f * m[c][i];
+                    inv[r][i] -= f * inv[c][i];
+                }
+            }
+        }
+
+        if !inv.iter().flatten().all(|v| v.is_finite()) {
+            return None;
+        }
+
+        Some(Matrix4::new(inv))
+    }
+
+    /// Transform the given point, performing the perspective division if
+    /// needed.
+    pub fn transform_point(&self, p: Vector3) -> Vector3 {
+        let m = &self.m;
+
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+
+        if w == 1.0 {
+            Vector3::new(x, y, z)
+        } else {
+            Vector3::new(x / w, y / w, z / w)
+        }
+    }
+
+    /// Transform the given direction, translations do not affect directions.
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let m = &self.m;
+
+        Vector3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Matrix4::identity()
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|i| self.m[r][i] * other.m[i][c]).sum();
+            }
+        }
+
+        Matrix4::new(m)
+    }
+}
+
+impl From<Quaternion> for Matrix4 {
+    fn from(q: Quaternion) -> Matrix4 {
+        q.to_matrix()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::{Matrix4, Vector3};
+
+    fn round(v: Vector3) -> Vector3 {
+        (v * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn test_rotations_match_vector3() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+
+        let mut expected = v;
+        expected.rotate_x(0.4);
+        assert_eq!(
+            round(Matrix4::rotation_x(0.4).transform_point(v)),
+            round(expected)
+        );
+
+        let mut expected = v;
+        expected.rotate_y(PI / 2.0);
+        assert_eq!(
+            round(Matrix4::rotation_y(PI / 2.0).transform_point(v)),
+            round(expected)
+        );
+
+        let mut expected = v;
+        expected.rotate_z(-2.0);
+        assert_eq!(
+            round(Matrix4::rotation_z(-2.0).transform_point(v)),
+            round(expected)
+        );
+
+        assert_eq!(
+            round(Matrix4::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), -2.0).transform_point(v)),
+            round(expected)
+        );
+    }
+
+    #[test]
+    fn test_composition() {
+        let m = Matrix4::translation(Vector3::new(1.0, 0.0, -1.0))
+            * Matrix4::scale(2.0)
+            * Matrix4::rotation_z(PI / 2.0);
+
+        assert_eq!(
+            round(m.transform_point(Vector3::new(1.0, 2.0, 3.0))),
+            Vector3::new(-3.0, 2.0, 5.0)
+        );
+        assert_eq!(
+            round(m.transform_vector(Vector3::new(1.0, 2.0, 3.0))),
+            Vector3::new(-4.0, 2.0, 6.0)
+        );
+
+        assert_eq!(Matrix4::identity() * m, m);
+        assert_eq!(m * Matrix4::identity(), m);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let m = Matrix4::translation(Vector3::new(1.0, 5.0, -1.0))
+            * Matrix4::scale(3.0)
+            * Matrix4::rotation_x(0.3)
+            * Matrix4::rotation_y(1.3);
+
+        let p = Vector3::new(-2.0, 4.0, 0.5);
+        assert_eq!(
+            round(m.inverse().unwrap().transform_point(m.transform_point(p))),
+            p
+        );
+
+        assert_eq!(Matrix4::scale(0.0).inverse(), None);
+        assert_eq!(Matrix4::scale(f32::NAN).inverse(), None);
+        assert_eq!(Matrix4::scale(f32::INFINITY).inverse(), None);
+    }
+
+    #[test]
+    fn test_transpose() {
+        let m = Matrix4::translation(Vector3::new(1.0, 2.0, 3.0));
+
+        assert_eq!(m.transpose().m[3], [1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(m.transpose().transpose(), m);
+    }
+
+    #[test]
+    fn test_look_at() {
+        assert_eq!(
+            Matrix4::look_at(
+                Vector3::new(0.0, 0.0, -10.0),
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, -1.0, 0.0)
+            ),
+            Matrix4::translation(Vector3::new(0.0, 0.0, 10.0))
+        );
+
+        let view = Matrix4::look_at(
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+        );
+        assert_eq!(
+            round(view.transform_point(Vector3::new(0.0, 0.0, 0.0))),
+            Vector3::new(0.0, 0.0, 10.0)
+        );
+        assert_eq!(
+            round(view.transform_point(Vector3::new(0.0, 0.0, 1.0))),
+            Vector3::new(1.0, 0.0, 10.0)
+        );
+    }
+
+    #[test]
+    fn test_projections() {
+        let p = Matrix4::perspective(PI / 2.0, 2.0, 1.0, 10.0);
+
+        assert_eq!(
+            round(p.transform_point(Vector3::new(0.0, 0.0, 1.0))),
+            Vector3::new(0.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            round(p.transform_point(Vector3::new(20.0, -10.0, 10.0))),
+            Vector3::new(1.0, -1.0, 1.0)
+        );
+
+        let o = Matrix4::orthographic(-2.0, 2.0, -1.0, 1.0, 0.0, 10.0);
+        assert_eq!(
+            round(o.transform_point(Vector3::new(2.0, -1.0, 5.0))),
+            Vector3::new(1.0, -1.0, 0.5)
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Indexed triangle meshes where the triangles share their vertices, useful to
+//! know which triangles are adjacent to each other.
+
+use std::collections::HashMap;
+
+use crate::{stl::Stl, Vector3};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedMesh {
+    pub vertices: Vec<Vector3>,
+    pub triangles: Vec<[usize; 3]>,
+
+    // the triangles around the vertex `v` are the ones in
+    // `vertex_faces[vertex_faces_start[v]..vertex_faces_start[v + 1]]`, storing
+    // them this way keeps cloning the mesh cheap.
+    vertex_faces_start: Vec<usize>,
+    vertex_faces: Vec<usize>,
+}
+
+/// An edge between two vertices of an IndexedMesh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub vertices: [usize; 2],
+
+    /// The triangles that share this edge, only one if it's on the boundary
+    /// of the mesh. More than two means that the mesh is not manifold.
+    pub faces: Vec<usize>,
+}
+
+impl IndexedMesh {
+    /// Create a new IndexedMesh from the given vertices and the triangles
+    /// made by the vertices at the given indices.
+    ///
+    /// Panics if any index is out of bounds.
+    pub fn new(vertices: Vec<Vector3>, triangles: Vec<[usize; 3]>) -> Self {
+        let mut vertex_faces_start = vec![0; vertices.len() + 1];
+        for &v in triangles.iter().flatten() {
+            vertex_faces_start[v + 1] += 1;
+        }
+        for v in 0..vertices.len() {
+            vertex_faces_start[v + 1] += vertex_faces_start[v];
+        }
+
+        let mut next = vertex_faces_start.clone();
+        let mut vertex_faces = vec![0; vertex_faces_start[vertices.len()]];
+        for (f, tri) in triangles.iter().enumerate() {
+            for &v in tri {
+                vertex_faces[next[v]] = f;
+                next[v] += 1;
+            }
+        }
+
+        IndexedMesh {
+            vertices,
+            triangles,
+            vertex_faces_start,
+            vertex_faces,
+        }
+    }
+
+    /// Create an IndexedMesh from a soup of triangles merging the vertices
+    /// that are exactly in the same position.
+    pub fn from_triangles(triangles: impl IntoIterator<Item = [Vector3; 3]>) -> Self {
+        let mut vertices = vec![];
+        let mut indices = HashMap::new();
+
+        let triangles = triangles
+            .into_iter()
+            .map(|tri| {
+                let mut t = [0; 3];
+
+                for (i, v) in tri.iter().enumerate() {
+                    // adding 0 turns -0 into 0 so that they're merged
+                    let key = (
+                        (v.x + 0.0).to_bits(),
+                        (v.y + 0.0).to_bits(),
+                        (v.z + 0.0).to_bits(),
+                    );
+
+                    t[i] = *indices.entry(key).or_insert_with(|| {
+                        vertices.push(*v);
+                        vertices.len() - 1
+                    });
+                }
+
+                t
+            })
+            .collect();
+
+        IndexedMesh::new(vertices, triangles)
+    }
+
+    /// The indices of the triangles that use the given vertex.
+    pub fn vertex_faces(&self, v: usize) -> &[usize] {
+        &self.vertex_faces[self.vertex_faces_start[v]..self.vertex_faces_start[v + 1]]
+    }
+
+    /// All the edges of the mesh, each one listed only once.
+    pub fn edges(&self) -> Vec<Edge> {
+        let mut edges = vec![];
+
+        for (f, tri) in self.triangles.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (tri[i], tri[(i + 1) % 3]);
+
+                let faces = self
+                    .vertex_faces(a)
+                    .iter()
+                    .cloned()
+                    .filter(|&g| self.triangles[g].contains(&b))
+                    .collect::<Vec<_>>();
+
+                // the edge belongs to the first triangle that has it
+                if faces[0] == f {
+                    edges.push(Edge {
+                        vertices: [a, b],
+                        faces,
+                    });
+                }
+            }
+        }
+
+        edges
+    }
+
+    /// The normal of the given triangle following the counterclockwise
+    /// winding order. Its length is twice the area of the triangle.
+    pub fn face_normal(&self, f: usize) -> Vector3 {
+        let [a, b, c] = self.triangles[f];
+        let (a, b, c) = (self.vertices[a], self.vertices[b], self.vertices[c]);
+
+        (b - a).cross(&(c - a))
+    }
+
+    /// The normal of each vertex as the average of the normals of the
+    /// triangles around it weighted by their area.
+    pub fn vertex_normals(&self) -> Vec<Vector3> {
+        let face_normals = (0..self.triangles.len())
+            .map(|f| self.face_normal(f))
+            .collect::<Vec<_>>();
+
+        (0..self.vertices.len())
+            .map(|v| {
+                self.vertex_faces(v)
+                    .iter()
+                    .fold(Vector3::new(0.0, 0.0, 0.0), |n, &f| n + face_normals[f])
+                    .normalized()
+            })
+            .collect()
+    }
+
+    /// The normals of the vertices of each triangle. Unlike `vertex_normals`,
+    /// only the triangles around a vertex whose normal differs by at most
+    /// `crease_angle` radians from the normal of the triangle are taken into
+    /// account so that sharp edges stay sharp.
+    pub fn corner_normals(&self, crease_angle: f32) -> Vec<[Vector3; 3]> {
+        let face_normals = (0..self.triangles.len())
+            .map(|f| self.face_normal(f))
+            .collect::<Vec<_>>();
+        let unit_normals = face_normals
+            .iter()
+            .map(Vector3::normalized)
+            .collect::<Vec<_>>();
+
+        let min_cos = crease_angle.cos();
+
+        self.triangles
+            .iter()
+            .enumerate()
+            .map(|(f, tri)| {
+                let mut normals = [unit_normals[f]; 3];
+
+                for (n, &v) in normals.iter_mut().zip(tri) {
+                    let smooth = self
+                        .vertex_faces(v)
+                        .iter()
+                        .filter(|&&g| unit_normals[g].dot(&unit_normals[f]) >= min_cos)
+                        .fold(Vector3::new(0.0, 0.0, 0.0), |n, &g| n + face_normals[g])
+                        .normalized();
+
+                    if smooth.norm() != 0.0 {
+                        *n = smooth;
+                    }
+                }
+
+                normals
+            })
+            .collect()
+    }
+}
+
+impl From<&Stl> for IndexedMesh {
+    fn from(stl: &Stl) -> Self {
+        IndexedMesh::from_triangles(stl.facets.iter().map(|f| f.vertices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::{Edge, IndexedMesh, Vector3};
+
+    fn round(v: Vector3) -> Vector3 {
+        (v * 1000.0).round() / 1000.0
+    }
+
+    // the two triangles share an edge and their normals are ~55 degrees apart
+    fn folded_square() -> IndexedMesh {
+        IndexedMesh::from_triangles(vec![
+            [
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            [
+                Vector3::new(1.0, 0.0, -0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(0.0, 0.0, -1.0),
+            ],
+        ])
+    }
+
+    #[test]
+    fn test_from_triangles() {
+        let mesh = folded_square();
+
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [1, 2, 3]]);
+
+        assert_eq!(mesh.vertex_faces(0), &[0]);
+        assert_eq!(mesh.vertex_faces(1), &[0, 1]);
+        assert_eq!(mesh.vertex_faces(2), &[0, 1]);
+        assert_eq!(mesh.vertex_faces(3), &[1]);
+    }
+
+    #[test]
+    fn test_edges() {
+        let mesh = folded_square();
+
+        assert_eq!(
+            mesh.edges(),
+            vec![
+                Edge {
+                    vertices: [0, 1],
+                    faces: vec![0]
+                },
+                Edge {
+                    vertices: [1, 2],
+                    faces: vec![0, 1]
+                },
+                Edge {
+                    vertices: [2, 0],
+                    faces: vec![0]
+                },
+                Edge {
+                    vertices: [2, 3],
+                    faces: vec![1]
+                },
+                Edge {
+                    vertices: [3, 1],
+                    faces: vec![1]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normals() {
+        let mesh = folded_square();
+
+        assert_eq!(mesh.face_normal(0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh.face_normal(1), Vector3::new(-1.0, -1.0, 1.0));
+
+        let normals = mesh.vertex_normals();
+        assert_eq!(normals[0], Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            round(normals[1]),
+            round(Vector3::new(-1.0, -1.0, 2.0).normalized())
+        );
+        assert_eq!(
+            round(normals[3]),
+            round(Vector3::new(-1.0, -1.0, 1.0).normalized())
+        );
+    }
+
+    #[test]
+    fn test_corner_normals() {
+        let mesh = folded_square();
+        let n0 = Vector3::new(0.0, 0.0, 1.0);
+        let n1 = Vector3::new(-1.0, -1.0, 1.0).normalized();
+
+        assert_eq!(mesh.corner_normals(PI / 4.0), vec![[n0; 3], [n1; 3]]);
+
+        let smooth = mesh.corner_normals(PI / 2.0);
+        assert_eq!(smooth[0][0], n0);
+        assert_eq!(round(smooth[0][1]), round(mesh.vertex_normals()[1]));
+        assert_eq!(smooth[0][1], smooth[1][0]);
+    }
+}
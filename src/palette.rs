@@ -0,0 +1,519 @@
+//! Colors and colormaps to turn values in the [0, 1] range, like depth or
+//! light, into colors that terminals can display.
+
+use std::fmt;
+
+/// A terminal color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// 24-bit color
+    Rgb(u8, u8, u8),
+
+    /// Index in the 256 colors palette
+    Ansi256(u8),
+
+    /// Index in the 16 basic colors palette, the bright colors come after the
+    /// normal ones.
+    Ansi16(u8),
+}
+
+/// The number of colors a terminal supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+/// A function from the [0, 1] range to colors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Colormap {
+    Grayscale,
+    Viridis,
+    Inferno,
+    Turbo,
+
+    /// Evenly spaced colors to interpolate between, there must be at least
+    /// one.
+    Gradient(Vec<(u8, u8, u8)>),
+}
+
+/// A colormap that produces colors for a given color depth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub colormap: Colormap,
+    pub depth: ColorDepth,
+
+    /// Whether the colors are displayed on a light background, in that case
+    /// the colormap is reversed so that high values are dark.
+    pub light_background: bool,
+}
+
+// the colors of the 16 colors palette as rendered by xterm
+static ANSI16_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// levels of each component in the 6x6x6 color cube of the 256 colors palette
+static ANSI256_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// the colormaps below are sampled at evenly spaced points from the matplotlib
+// ones.
+
+static VIRIDIS: [(u8, u8, u8); 9] = [
+    (68, 1, 84),
+    (71, 44, 122),
+    (59, 81, 139),
+    (44, 113, 142),
+    (33, 144, 141),
+    (39, 173, 129),
+    (92, 200, 99),
+    (170, 220, 50),
+    (253, 231, 37),
+];
+
+static INFERNO: [(u8, u8, u8); 9] = [
+    (0, 0, 4),
+    (31, 12, 72),
+    (85, 15, 109),
+    (136, 34, 106),
+    (186, 54, 85),
+    (227, 89, 51),
+    (249, 140, 10),
+    (249, 201, 50),
+    (252, 255, 164),
+];
+
+static TURBO: [(u8, u8, u8); 9] = [
+    (48, 18, 59),
+    (70, 107, 227),
+    (40, 188, 235),
+    (50, 242, 152),
+    (164, 252, 60),
+    (239, 205, 58),
+    (251, 128, 34),
+    (204, 50, 6),
+    (122, 4, 3),
+];
+
+impl Color {
+    /// Convert the color to the closest one that can be displayed with the
+    /// given color depth. Colors are never converted to a richer depth.
+    pub fn quantize(self, depth: ColorDepth) -> Color {
+        match (self, depth) {
+            (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Ansi256(rgb_to_ansi256(r, g, b)),
+            (Color::Rgb(r, g, b), ColorDepth::Ansi16) => Color::Ansi16(rgb_to_ansi16(r, g, b)),
+            (Color::Ansi256(c), ColorDepth::Ansi16) => {
+                let (r, g, b) = ansi256_to_rgb(c);
+                Color::Ansi16(rgb_to_ansi16(r, g, b))
+            }
+            (c, _) => c,
+        }
+    }
+
+    /// The red, green and blue components of the color, the indexed colors
+    /// are assumed to have the xterm default values.
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Ansi256(c) => ansi256_to_rgb(c),
+            Color::Ansi16(c) => ANSI16_RGB[usize::from(c)],
+        }
+    }
+}
+
+impl ColorDepth {
+    /// Detect the color depth of the terminal from the environment, `None` if
+    /// colors should not be used at all.
+    pub fn detect() -> Option<ColorDepth> {
+        let var = |name| std::env::var(name).ok();
+
+        ColorDepth::from_env(
+            var("COLORTERM").as_deref(),
+            var("TERM").as_deref(),
+            var("NO_COLOR").is_some_and(|v| !v.is_empty()),
+        )
+    }
+
+    /// Like `detect`, but with the values of the `COLORTERM`, `TERM` and
+    /// `NO_COLOR` environment variables passed explicitly.
+    pub fn from_env(colorterm: Option<&str>, term: Option<&str>, no_color: bool) -> Option<Self> {
+        if no_color {
+            return None;
+        }
+
+        let term = term.unwrap_or("");
+        if term == "dumb" {
+            return None;
+        }
+
+        if matches!(colorterm, Some("truecolor") | Some("24bit")) || term.ends_with("-direct") {
+            return Some(ColorDepth::TrueColor);
+        }
+
+        if term.is_empty() {
+            return None;
+        }
+
+        // pretty much every terminal emulator supports 256 colors even when
+        // it says just `xterm` or `*-color`, only the consoles and the real
+        // terminals of old are limited to 16.
+        let limited = matches!(
+            term,
+            "linux" | "vt100" | "vt102" | "vt220" | "ansi" | "cons25" | "cygwin"
+        ) || term.ends_with("-16color");
+
+        if limited {
+            Some(ColorDepth::Ansi16)
+        } else {
+            Some(ColorDepth::Ansi256)
+        }
+    }
+}
+
+impl termion::color::Color for Color {
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Color::Rgb(r, g, b) => termion::color::Rgb(r, g, b).write_fg(f),
+            Color::Ansi256(c) => termion::color::AnsiValue(c).write_fg(f),
+            Color::Ansi16(c) if c < 8 => write!(f, "\x1b[{}m", 30 + u16::from(c)),
+            Color::Ansi16(c) => write!(f, "\x1b[{}m", 90 + u16::from(c - 8)),
+        }
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Color::Rgb(r, g, b) => termion::color::Rgb(r, g, b).write_bg(f),
+            Color::Ansi256(c) => termion::color::AnsiValue(c).write_bg(f),
+            Color::Ansi16(c) if c < 8 => write!(f, "\x1b[{}m", 40 + u16::from(c)),
+            Color::Ansi16(c) => write!(f, "\x1b[{}m", 100 + u16::from(c - 8)),
+        }
+    }
+}
+
+impl Colormap {
+    /// The color at the given position, `t` is clamped in the [0, 1] range.
+    pub fn color(&self, t: f32) -> Color {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+
+        let (r, g, b) = match self {
+            Colormap::Grayscale => gray(t, 4),
+            Colormap::Viridis => interpolate(&VIRIDIS, t),
+            Colormap::Inferno => interpolate(&INFERNO, t),
+            Colormap::Turbo => interpolate(&TURBO, t),
+            Colormap::Gradient(colors) => interpolate(colors, t),
+        };
+
+        Color::Rgb(r, g, b)
+    }
+}
+
+impl std::str::FromStr for Colormap {
+    type Err = String;
+
+    /// Parse either the name of a colormap or a gradient in the
+    /// `#rrggbb,#rrggbb,...` format.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grayscale" => Ok(Colormap::Grayscale),
+            "viridis" => Ok(Colormap::Viridis),
+            "inferno" => Ok(Colormap::Inferno),
+            "turbo" => Ok(Colormap::Turbo),
+            _ => s
+                .split(',')
+                .map(|c| parse_hex_color(c.trim()))
+                .collect::<Option<Vec<_>>>()
+                .map(Colormap::Gradient)
+                .ok_or_else(|| {
+                    "expected `grayscale`, `viridis`, `inferno`, `turbo` or a list of colors \
+                     like `#000080,#ffffff`"
+                        .to_string()
+                }),
+        }
+    }
+}
+
+impl Palette {
+    pub fn new(colormap: Colormap, depth: ColorDepth) -> Self {
+        Palette {
+            colormap,
+            depth,
+            light_background: false,
+        }
+    }
+
+    /// The color for the given value in the [0, 1] range.
+    pub fn color(&self, t: f32) -> Color {
+        if !self.light_background {
+            return self.colormap.color(t).quantize(self.depth);
+        }
+
+        let t = 1.0 - if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+
+        match self.colormap {
+            // the darkest grays are the most visible on a light background
+            Colormap::Grayscale => {
+                let (r, g, b) = gray(t, 0);
+                Color::Rgb(r, g, b).quantize(self.depth)
+            }
+            _ => self.colormap.color(t).quantize(self.depth),
+        }
+    }
+
+    /// The color to use as background for the colors of the palette.
+    pub fn background(&self) -> Color {
+        let color = if self.light_background {
+            Color::Ansi256(255)
+        } else {
+            Color::Ansi256(235)
+        };
+
+        match (color, self.depth) {
+            (Color::Ansi256(c), ColorDepth::TrueColor) => {
+                let (r, g, b) = ansi256_to_rgb(c);
+                Color::Rgb(r, g, b)
+            }
+            _ => color.quantize(self.depth),
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new(Colormap::Grayscale, ColorDepth::Ansi256)
+    }
+}
+
+// Stick to 20 levels of the grayscale ramp of the 256 colors palette starting
+// from the given one, this way it looks the same on every terminal.
+fn gray(t: f32, darkest: u8) -> (u8, u8, u8) {
+    let v = 8 + 10 * (darkest + (t * 19.0).round() as u8);
+    (v, v, v)
+}
+
+fn interpolate(colors: &[(u8, u8, u8)], t: f32) -> (u8, u8, u8) {
+    if colors.len() < 2 {
+        return colors.first().cloned().unwrap_or((0, 0, 0));
+    }
+
+    let pos = t * (colors.len() - 1) as f32;
+    let i = (pos.floor() as usize).min(colors.len() - 2);
+    let f = pos - i as f32;
+
+    let lerp = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * f).round() as u8;
+
+    let ((r0, g0, b0), (r1, g1, b1)) = (colors[i], colors[i + 1]);
+    (lerp(r0, r1), lerp(g0, g1), lerp(b0, b1))
+}
+
+fn parse_hex_color(s: &str) -> Option<(u8, u8, u8)> {
+    let s = s.strip_prefix('#')?;
+    if s.len() != 6 || !s.is_ascii() {
+        return None;
+    }
+
+    let component = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).ok();
+    Some((component(0)?, component(2)?, component(4)?))
+}
+
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    // grays are better represented by the grayscale ramp which has more levels
+    if r == g && g == b {
+        return match r {
+            0..=7 => 16,
+            239..=255 => 231,
+            _ => 232 + ((f32::from(r) - 8.0) / 10.0).round() as u8,
+        };
+    }
+
+    let level = |c: u8| match c {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (c - 35) / 40,
+    };
+
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn ansi256_to_rgb(c: u8) -> (u8, u8, u8) {
+    match c {
+        0..=15 => ANSI16_RGB[usize::from(c)],
+        16..=231 => {
+            let c = usize::from(c - 16);
+            (
+                ANSI256_CUBE_LEVELS[c / 36],
+                ANSI256_CUBE_LEVELS[(c / 6) % 6],
+                ANSI256_CUBE_LEVELS[c % 6],
+            )
+        }
+        _ => {
+            let v = 8 + 10 * (c - 232);
+            (v, v, v)
+        }
+    }
+}
+
+fn rgb_to_ansi16(r: u8, g: u8, b: u8) -> u8 {
+    let dist = |&(r1, g1, b1): &(u8, u8, u8)| {
+        let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        d(r, r1) + d(g, g1) + d(b, b1)
+    };
+
+    (0..16_u8)
+        .min_by_key(|&i| dist(&ANSI16_RGB[usize::from(i)]))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, ColorDepth, Colormap, Palette};
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(ColorDepth::from_env(None, None, false), None);
+        assert_eq!(ColorDepth::from_env(None, Some("dumb"), false), None);
+        assert_eq!(
+            ColorDepth::from_env(Some("truecolor"), Some("xterm-256color"), true),
+            None
+        );
+
+        assert_eq!(
+            ColorDepth::from_env(None, Some("linux"), false),
+            Some(ColorDepth::Ansi16)
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("vt100"), false),
+            Some(ColorDepth::Ansi16)
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm"), false),
+            Some(ColorDepth::Ansi256)
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm-color"), false),
+            Some(ColorDepth::Ansi256)
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("screen-256color"), false),
+            Some(ColorDepth::Ansi256)
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm-direct"), false),
+            Some(ColorDepth::TrueColor)
+        );
+        assert_eq!(
+            ColorDepth::from_env(Some("24bit"), Some("xterm"), false),
+            Some(ColorDepth::TrueColor)
+        );
+    }
+
+    #[test]
+    fn test_light_background() {
+        let mut palette = Palette::default();
+        assert_eq!(palette.background(), Color::Ansi256(235));
+
+        palette.light_background = true;
+        assert_eq!(palette.background(), Color::Ansi256(255));
+        assert_eq!(palette.color(1.0), Color::Ansi256(232));
+        assert_eq!(palette.color(0.0), Color::Ansi256(251));
+
+        palette.depth = ColorDepth::TrueColor;
+        assert_eq!(palette.background(), Color::Rgb(238, 238, 238));
+
+        palette.depth = ColorDepth::Ansi16;
+        assert_eq!(palette.background(), Color::Ansi16(7));
+        assert_eq!(palette.color(1.0), Color::Ansi16(0));
+    }
+
+    #[test]
+    fn test_quantize() {
+        let c = Color::Rgb(255, 0, 0);
+        assert_eq!(c.quantize(ColorDepth::TrueColor), c);
+        assert_eq!(c.quantize(ColorDepth::Ansi256), Color::Ansi256(196));
+        assert_eq!(c.quantize(ColorDepth::Ansi16), Color::Ansi16(9));
+
+        assert_eq!(
+            Color::Rgb(98, 98, 98).quantize(ColorDepth::Ansi256),
+            Color::Ansi256(241)
+        );
+        assert_eq!(
+            Color::Rgb(0, 0, 0).quantize(ColorDepth::Ansi256),
+            Color::Ansi256(16)
+        );
+        assert_eq!(
+            Color::Rgb(100, 140, 250).quantize(ColorDepth::Ansi256),
+            Color::Ansi256(69)
+        );
+
+        assert_eq!(
+            Color::Ansi256(231).quantize(ColorDepth::Ansi16),
+            Color::Ansi16(15)
+        );
+        assert_eq!(
+            Color::Ansi16(3).quantize(ColorDepth::TrueColor),
+            Color::Ansi16(3)
+        );
+    }
+
+    #[test]
+    fn test_escape_codes() {
+        use termion::color::{Bg, Fg};
+
+        assert_eq!(Fg(Color::Rgb(1, 2, 3)).to_string(), "\x1b[38;2;1;2;3m");
+        assert_eq!(Fg(Color::Ansi256(42)).to_string(), "\x1b[38;5;42m");
+        assert_eq!(Fg(Color::Ansi16(1)).to_string(), "\x1b[31m");
+        assert_eq!(Fg(Color::Ansi16(9)).to_string(), "\x1b[91m");
+        assert_eq!(Bg(Color::Ansi16(9)).to_string(), "\x1b[101m");
+    }
+
+    #[test]
+    fn test_colormaps() {
+        assert_eq!(Colormap::Viridis.color(0.0), Color::Rgb(68, 1, 84));
+        assert_eq!(Colormap::Viridis.color(1.0), Color::Rgb(253, 231, 37));
+        assert_eq!(Colormap::Viridis.color(2.0), Color::Rgb(253, 231, 37));
+
+        let gradient = Colormap::Gradient(vec![(0, 0, 0), (200, 100, 0)]);
+        assert_eq!(gradient.color(0.5), Color::Rgb(100, 50, 0));
+        assert_eq!(
+            Colormap::Gradient(vec![(1, 2, 3)]).color(0.7),
+            Color::Rgb(1, 2, 3)
+        );
+
+        // the grayscale colormap covers the 256 colors ramp from 236 to 255
+        let palette = Palette::default();
+        assert_eq!(palette.color(0.0), Color::Ansi256(236));
+        assert_eq!(palette.color(1.0), Color::Ansi256(255));
+        for i in 0..=19 {
+            assert_eq!(
+                palette.color(i as f32 / 19.0),
+                Color::Ansi256(236 + i as u8)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_colormap() {
+        assert_eq!("turbo".parse(), Ok(Colormap::Turbo));
+        assert_eq!(
+            "#000080, #FFffff".parse(),
+            Ok(Colormap::Gradient(vec![(0, 0, 128), (255, 255, 255)]))
+        );
+        assert!("#00008".parse::<Colormap>().is_err());
+        assert!("rainbow".parse::<Colormap>().is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! A simple unit Quaternion to represent rotations
+
+use std::ops::Mul;
+
+use crate::{Matrix4, Vector3};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    /// Create a new Quaternion from the given components
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Quaternion { w, x, y, z }
+    }
+
+    /// The Quaternion that represents no rotation at all
+    pub fn identity() -> Self {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Create a Quaternion that rotates around the given axis by the given
+    /// angle in radians. The axis doesn't need to be normalized.
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        let axis = axis.normalized();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+
+        Quaternion::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+
+    /// Create a Quaternion that rotates around the X axis first, then around
+    /// the Y axis and finally around the Z axis by the given angles in
+    /// radians.
+    pub fn from_euler(rotation_x: f32, rotation_y: f32, rotation_z: f32) -> Self {
+        Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), rotation_z)
+            * Quaternion::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), rotation_y)
+            * Quaternion::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), rotation_x)
+    }
+
+    /// Length of the Quaternion, it's 1 for Quaternions representing
+    /// rotations
+    pub fn norm(&self) -> f32 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    /// Create a new Quaternion with unit length. Useful to fight precision
+    /// errors after composing a lot of rotations.
+    pub fn normalized(&self) -> Self {
+        let n = self.norm();
+
+        if n == 0.0 {
+            Quaternion::identity()
+        } else {
+            Quaternion::new(self.w / n, self.x / n, self.y / n, self.z / n)
+        }
+    }
+
+    /// The conjugate of the Quaternion which, for unit Quaternions, is also
+    /// the rotation in the opposite direction.
+    pub fn conjugate(&self) -> Self {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// The inverse of the Quaternion, `None` if the Quaternion is zero.
+    pub fn inverse(&self) -> Option<Self> {
+        let n2 = self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z;
+
+        if n2 == 0.0 {
+            return None;
+        }
+
+        let c = self.conjugate();
+        Some(Quaternion::new(c.w / n2, c.x / n2, c.y / n2, c.z / n2))
+    }
+
+    /// Get the rotation axis and the angle in radians this Quaternion
+    /// represents. The axis of the identity rotation is arbitrarily the X
+    /// axis.
+    pub fn axis_angle(&self) -> (Vector3, f32) {
+        let q = self.normalized();
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        let axis = Vector3::new(q.x, q.y, q.z);
+
+        if axis.norm() == 0.0 {
+            (Vector3::new(1.0, 0.0, 0.0), 0.0)
+        } else {
+            (axis.normalized(), angle)
+        }
+    }
+
+    /// Rotate the given vector by this Quaternion
+    pub fn rotate(&self, v: Vector3) -> Vector3 {
+        let u = Vector3::new(self.x, self.y, self.z);
+        let t = u.cross(&v) * 2.0;
+
+        v + t * self.w + u.cross(&t)
+    }
+
+    /// Convert the Quaternion in the equivalent rotation matrix
+    pub fn to_matrix(&self) -> Matrix4 {
+        let Quaternion { w, x, y, z } = self.normalized();
+
+        Matrix4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::identity()
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    /// Compose two rotations, the resulting Quaternion rotates by `other`
+    /// first and then by `self`.
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::{Quaternion, Vector3};
+
+    fn round(v: Vector3) -> Vector3 {
+        (v * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn test_rotate_matches_vector3() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+
+        let mut expected = v;
+        expected.rotate_x(PI / 3.0);
+        assert_eq!(
+            round(Quaternion::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), PI / 3.0).rotate(v)),
+            round(expected)
+        );
+
+        let mut expected = v;
+        expected.rotate_y(-PI / 5.0);
+        assert_eq!(
+            round(Quaternion::from_axis_angle(Vector3::new(0.0, 2.0, 0.0), -PI / 5.0).rotate(v)),
+            round(expected)
+        );
+
+        let mut expected = v;
+        expected.rotate_z(PI);
+        assert_eq!(
+            round(Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), PI).rotate(v)),
+            round(expected)
+        );
+    }
+
+    #[test]
+    fn test_euler() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+
+        let mut expected = v;
+        expected.rotate_x(0.3);
+        expected.rotate_y(-1.2);
+        expected.rotate_z(2.0);
+
+        assert_eq!(
+            round(Quaternion::from_euler(0.3, -1.2, 2.0).rotate(v)),
+            round(expected)
+        );
+    }
+
+    #[test]
+    fn test_composition_and_inverse() {
+        let q = Quaternion::from_axis_angle(Vector3::new(1.0, 1.0, 0.0), 0.7);
+        let v = Vector3::new(-4.0, 2.0, 0.5);
+
+        assert_eq!(round((q.conjugate() * q).rotate(v)), round(v));
+        assert_eq!(round((q.inverse().unwrap() * q).rotate(v)), round(v));
+        assert_eq!(round((q * q).rotate(v)), round(q.rotate(q.rotate(v))));
+
+        assert_eq!(Quaternion::new(0.0, 0.0, 0.0, 0.0).inverse(), None);
+    }
+
+    #[test]
+    fn test_axis_angle() {
+        let (axis, angle) =
+            Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 2.0), PI / 2.0).axis_angle();
+
+        assert_eq!(round(axis), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!((angle * 1000.0).round(), (PI / 2.0 * 1000.0).round());
+
+        assert_eq!(
+            Quaternion::identity().axis_angle(),
+            (Vector3::new(1.0, 0.0, 0.0), 0.0)
+        );
+    }
+
+    #[test]
+    fn test_to_matrix() {
+        let q = Quaternion::from_euler(0.1, 0.2, 0.3);
+        let v = Vector3::new(5.0, -1.0, 2.0);
+
+        assert_eq!(round(q.to_matrix().transform_point(v)), round(q.rotate(v)));
+    }
+}
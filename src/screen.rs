@@ -0,0 +1,328 @@
+//! A model of the terminal screen that remembers the last frame written to it,
+//! so that drawing the next one only writes the cells that changed. Escape
+//! sequences to change colors are only written when the color actually
+//! changes, that is consecutive cells of the same color share them.
+
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+use termion::color::{Fg, Reset};
+
+use crate::frame::{background_escape, Cell};
+use crate::palette::Color;
+
+#[derive(Debug)]
+pub struct Screen {
+    // the rows shown on the screen starting from the top, the cells past the
+    // end of a row are empty.
+    shown: Vec<Vec<Cell>>,
+
+    // the rows of the frame to show next, see `present`
+    next: Vec<Vec<Cell>>,
+
+    background: Option<Color>,
+
+    // whether the screen must be cleared before presenting the next frame
+    // because what's shown is unknown.
+    dirty: bool,
+
+    pen: Pen,
+}
+
+impl Screen {
+    /// Create a Screen whose content is unknown, the first frame clears it.
+    pub fn new() -> Self {
+        Screen {
+            shown: vec![],
+            next: vec![],
+            background: None,
+            dirty: true,
+            pen: Pen {
+                cursor: None,
+                fg: None,
+                bg: None,
+                background: None,
+            },
+        }
+    }
+
+    /// Forget what's shown on screen so that the next frame is drawn from
+    /// scratch, for example because the terminal was resized.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Set the color of the background of the screen, `None` for the default
+    /// one of the terminal. Changing it redraws the whole screen.
+    pub fn set_background(&mut self, background: Option<Color>) {
+        if background != self.background {
+            self.background = background;
+            self.dirty = true;
+        }
+    }
+
+    /// Clear the screen right away painting its background and move the
+    /// cursor to the top left corner. What's written afterwards, before the
+    /// next frame is presented, is never overwritten unless it's at the
+    /// position of a cell of that frame.
+    pub fn clear<W: Write>(&mut self, w: &mut W) -> io::Result<()> {
+        write!(
+            w,
+            "{}{}{}{}",
+            background_escape(self.background),
+            Fg(Reset),
+            termion::clear::All,
+            termion::cursor::Goto(1, 1),
+        )?;
+
+        self.shown.clear();
+        self.dirty = false;
+        // the cursor is moved by anything that is written before presenting
+        // the next frame
+        self.pen = Pen {
+            cursor: None,
+            fg: None,
+            bg: self.background,
+            background: self.background,
+        };
+
+        Ok(())
+    }
+
+    /// Set the cells of the given row of the next frame, starting from the
+    /// left. The rows that are not set are left empty.
+    pub fn set_row(&mut self, row: usize, cells: Vec<Cell>) {
+        if self.next.len() <= row {
+            self.next.resize(row + 1, vec![]);
+        }
+
+        self.next[row] = cells;
+    }
+
+    /// Write the next frame replacing the one shown on screen. Only the cells
+    /// that changed are written.
+    pub fn present<W: Write>(&mut self, w: &mut W) -> io::Result<()> {
+        if self.dirty {
+            self.clear(w)?;
+        }
+
+        let pen = &mut self.pen;
+        let next = std::mem::take(&mut self.next);
+        for (r, row) in next.iter().enumerate() {
+            let old = self.shown.get(r).map_or(&[][..], |row| &row[..]);
+
+            for (c, cell) in row.iter().enumerate() {
+                let unchanged = match old.get(c) {
+                    Some(old) => old.looks_like(cell),
+                    None => cell.is_blank() && cell.bg.is_none(),
+                };
+                if unchanged {
+                    continue;
+                }
+
+                pen.write(w, r, c, cell)?;
+            }
+
+            if old.len() > row.len() {
+                pen.clear_after(w, r, row.len())?;
+            }
+        }
+
+        for (r, old) in self.shown.iter().enumerate().skip(next.len()) {
+            if !old.is_empty() {
+                pen.clear_after(w, r, 0)?;
+            }
+        }
+
+        self.shown = next;
+        w.flush()
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The state of the terminal as left by the last frame, to only write the
+// escape sequences that change it.
+#[derive(Debug)]
+struct Pen {
+    // position of the cursor as (row, column), if known
+    cursor: Option<(usize, usize)>,
+    fg: Option<Color>,
+    bg: Option<Color>,
+    background: Option<Color>,
+}
+
+impl Pen {
+    fn write<W: Write>(
+        &mut self,
+        w: &mut W,
+        row: usize,
+        col: usize,
+        cell: &Cell,
+    ) -> io::Result<()> {
+        self.goto(w, row, col)?;
+
+        if cell.fg != self.fg && !cell.is_blank() {
+            self.fg = cell.fg;
+            match cell.fg {
+                None => write!(w, "{}", Fg(Reset))?,
+                Some(fg) => write!(w, "{}", Fg(fg))?,
+            }
+        }
+        self.set_bg(w, cell.bg.or(self.background))?;
+
+        write!(w, "{}", cell.glyph)?;
+        self.cursor = Some((row, col + 1));
+
+        Ok(())
+    }
+
+    // erase the given row from the given column onwards
+    fn clear_after<W: Write>(&mut self, w: &mut W, row: usize, col: usize) -> io::Result<()> {
+        self.goto(w, row, col)?;
+        self.set_bg(w, self.background)?;
+
+        write!(w, "{}", termion::clear::UntilNewline)
+    }
+
+    fn goto<W: Write>(&mut self, w: &mut W, row: usize, col: usize) -> io::Result<()> {
+        if self.cursor == Some((row, col)) {
+            return Ok(());
+        }
+
+        self.cursor = Some((row, col));
+        write!(
+            w,
+            "{}",
+            termion::cursor::Goto(to_coordinate(col), to_coordinate(row))
+        )
+    }
+
+    fn set_bg<W: Write>(&mut self, w: &mut W, bg: Option<Color>) -> io::Result<()> {
+        if bg == self.bg {
+            return Ok(());
+        }
+
+        self.bg = bg;
+        write!(w, "{}", background_escape(bg))
+    }
+}
+
+// terminal coordinates start from 1
+fn to_coordinate(i: usize) -> u16 {
+    u16::try_from(i + 1).unwrap_or(u16::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cell, Color, Screen};
+
+    use termion::color::{Bg, Fg, Reset};
+    use termion::cursor::Goto;
+
+    fn present(screen: &mut Screen) -> String {
+        let mut out = vec![];
+        screen.present(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn colored(s: &str, fg: Option<Color>) -> Vec<Cell> {
+        s.chars()
+            .map(|ch| Cell {
+                fg,
+                ..Cell::new(ch)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_present() {
+        let red = Color::Ansi256(196);
+        let mut screen = Screen::new();
+
+        // the first frame clears the screen, the blank cells are already
+        // there.
+        screen.set_row(0, colored("ab c", Some(red)));
+        screen.set_row(2, colored("de", None));
+        assert_eq!(
+            present(&mut screen),
+            format!(
+                "{}{}{}{}{}{}ab{}c{}{}de",
+                Bg(Reset),
+                Fg(Reset),
+                termion::clear::All,
+                Goto(1, 1),
+                Goto(1, 1),
+                Fg(red),
+                Goto(4, 1),
+                Goto(1, 3),
+                Fg(Reset),
+            )
+        );
+
+        // only what changed is written
+        screen.set_row(0, colored("ab c", Some(red)));
+        screen.set_row(2, colored("dx", None));
+        assert_eq!(present(&mut screen), format!("{}x", Goto(2, 3)));
+
+        screen.set_row(0, colored("ab c", Some(red)));
+        screen.set_row(2, colored("dx", None));
+        assert_eq!(present(&mut screen), "");
+
+        // cells and rows that are not there anymore are erased
+        screen.set_row(0, colored("a", Some(red)));
+        assert_eq!(
+            present(&mut screen),
+            format!(
+                "{}{}{}{}",
+                Goto(2, 1),
+                termion::clear::UntilNewline,
+                Goto(1, 3),
+                termion::clear::UntilNewline,
+            )
+        );
+    }
+
+    #[test]
+    fn test_background() {
+        let gray = Color::Ansi256(235);
+        let mut screen = Screen::new();
+
+        screen.set_row(0, colored("a", None));
+        present(&mut screen);
+
+        screen.set_background(Some(gray));
+        screen.set_row(0, colored("a", None));
+        assert_eq!(
+            present(&mut screen),
+            format!(
+                "{}{}{}{}{}a",
+                Bg(gray),
+                Fg(Reset),
+                termion::clear::All,
+                Goto(1, 1),
+                Goto(1, 1),
+            )
+        );
+
+        // cells with their own background restore the one of the screen
+        screen.set_row(
+            0,
+            vec![
+                Cell {
+                    bg: Some(Color::Ansi256(0)),
+                    ..Cell::new('a')
+                },
+                Cell::new('b'),
+            ],
+        );
+        assert_eq!(
+            present(&mut screen),
+            format!("{}{}a{}b", Goto(1, 1), Bg(Color::Ansi256(0)), Bg(gray))
+        );
+    }
+}
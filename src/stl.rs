@@ -60,6 +60,16 @@ impl Stl {
         Ok(Stl { header, facets })
     }
 
+    /// Remove the facets with any vertex that is NaN or infinite, they cannot
+    /// be displayed anyway. Returns how many facets were removed.
+    pub fn remove_non_finite(&mut self) -> usize {
+        let len = self.facets.len();
+        self.facets
+            .retain(|f| f.vertices.iter().all(|v| v.is_finite()));
+
+        len - self.facets.len()
+    }
+
     pub fn vertices(&self) -> impl Iterator<Item = &Vector3> {
         self.facets.iter().flat_map(|f| &f.vertices)
     }
@@ -213,4 +223,29 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_remove_non_finite() {
+        let facet = |x| Facet {
+            vertices: [
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(x, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            normal: Vector3::new(0.0, 0.0, 1.0),
+        };
+
+        let mut stl = Stl {
+            header: [0; 80],
+            facets: vec![
+                facet(1.0),
+                facet(f32::NAN),
+                facet(f32::INFINITY),
+                facet(1e30),
+            ],
+        };
+
+        assert_eq!(stl.remove_non_finite(), 2);
+        assert_eq!(stl.facets, vec![facet(1.0), facet(1e30)]);
+    }
 }
@@ -1,6 +1,6 @@
 //! A simple Vector3
 
-use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Sub};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vector3 {
@@ -20,6 +20,45 @@ impl Vector3 {
         Vector3::new(self.x.round(), self.y.round(), self.z.round())
     }
 
+    /// Whether all the coordinates are neither NaN nor infinite
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+
+    /// Dot product between two vectors
+    pub fn dot(&self, other: &Vector3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Cross product between two vectors
+    pub fn cross(&self, other: &Vector3) -> Vector3 {
+        Vector3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// Length of the vector
+    pub fn norm(&self) -> f32 {
+        // the squares of big coordinates easily overflow a f32, even when the
+        // length itself doesn't
+        let (x, y, z) = (f64::from(self.x), f64::from(self.y), f64::from(self.z));
+        (x * x + y * y + z * z).sqrt() as f32
+    }
+
+    /// Create a new Vector3 with the same direction but unit length. A zero
+    /// vector is returned as is.
+    pub fn normalized(&self) -> Self {
+        let n = self.norm();
+
+        if n == 0.0 {
+            *self
+        } else {
+            *self / n
+        }
+    }
+
     /// Rotate the point around the X axis by the given angle in radians
     pub fn rotate_x(&mut self, angle: f32) {
         let cosa = angle.cos();
@@ -89,6 +128,14 @@ impl Div<f32> for Vector3 {
     }
 }
 
+impl Neg for Vector3 {
+    type Output = Vector3;
+
+    fn neg(self) -> Vector3 {
+        Vector3::new(-self.x, -self.y, -self.z)
+    }
+}
+
 impl AddAssign for Vector3 {
     fn add_assign(&mut self, other: Vector3) {
         self.x += other.x;
@@ -201,5 +248,43 @@ mod tests {
         assert_eq!(v, Vector3::new(-6.0, 6.0, 9.0));
 
         assert_eq!(v / 3.0, Vector3::new(-2.0, 2.0, 3.0));
+
+        assert_eq!(-v, Vector3::new(6.0, -6.0, -9.0));
+    }
+
+    #[test]
+    fn test_products() {
+        let x = Vector3::new(1.0, 0.0, 0.0);
+        let y = Vector3::new(0.0, 1.0, 0.0);
+
+        assert_eq!(x.dot(&y), 0.0);
+        assert_eq!(
+            Vector3::new(1.0, 2.0, 3.0).dot(&Vector3::new(4.0, -5.0, 6.0)),
+            12.0
+        );
+
+        assert_eq!(x.cross(&y), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(y.cross(&x), Vector3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_is_finite() {
+        assert!(Vector3::new(1.0, -2.0, 1e30).is_finite());
+        assert!(!Vector3::new(f32::NAN, 0.0, 0.0).is_finite());
+        assert!(!Vector3::new(0.0, f32::INFINITY, 0.0).is_finite());
+        assert!(!Vector3::new(0.0, 0.0, f32::NEG_INFINITY).is_finite());
+    }
+
+    #[test]
+    fn test_norm() {
+        assert_eq!(Vector3::new(3.0, 0.0, 4.0).norm(), 5.0);
+        assert_eq!(
+            Vector3::new(3.0, 0.0, 4.0).normalized(),
+            Vector3::new(0.6, 0.0, 0.8)
+        );
+        assert_eq!(
+            Vector3::new(0.0, 0.0, 0.0).normalized(),
+            Vector3::new(0.0, 0.0, 0.0)
+        );
     }
 }